@@ -0,0 +1,366 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::parse::ParseStream;
+use syn::spanned::Spanned;
+use syn::{Attribute, Data, DeriveInput, Expr, Fields, Ident, LitStr, Member, Token, Type};
+
+/// Options of the `#[error(...)]` attributes of a type or variant.
+#[derive(Default)]
+struct Attrs {
+    /// Format string of the `Display` impl, which may refer to the fields.
+    message: Option<LitStr>,
+    /// Forwards `Display` and `source` to the only field.
+    transparent: bool,
+    code: Option<Expr>,
+    kind: Option<Expr>,
+}
+
+impl Attrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut this = Self::default();
+        for attr in attrs.iter().filter(|v| v.path().is_ident("error")) {
+            attr.parse_args_with(|input: ParseStream| this.parse_args(input))?;
+        }
+        Ok(this)
+    }
+
+    fn parse_args(&mut self, input: ParseStream) -> syn::Result<()> {
+        if input.peek(LitStr) {
+            let message = input.parse::<LitStr>()?;
+            if !input.is_empty() && !(input.peek2(Ident) && input.peek3(Token![=])) {
+                let span = message.span();
+                let message = "extra format arguments are not supported, refer to the fields \
+                    in the message instead, e.g. `{0}` or `{name}`";
+                return Err(syn::Error::new(span, message));
+            }
+            self.message = Some(message);
+        } else if input.peek(Ident) && input.fork().parse::<Ident>()? == "transparent" {
+            input.parse::<Ident>()?;
+            self.transparent = true;
+        } else {
+            return self.parse_options(input);
+        }
+        if input.is_empty() {
+            return Ok(());
+        }
+        input.parse::<Token![,]>()?;
+        self.parse_options(input)
+    }
+
+    /// Parses `code = ...` and `kind = ...`, separated by commas.
+    fn parse_options(&mut self, input: ParseStream) -> syn::Result<()> {
+        while !input.is_empty() {
+            let name = input.parse::<Ident>()?;
+            input.parse::<Token![=]>()?;
+            if name == "code" {
+                self.code = Some(input.parse()?);
+            } else if name == "kind" {
+                self.kind = Some(crate::error_kind(input.parse()?));
+            } else {
+                let message = "unsupported error option, expected a message, `transparent`, \
+                    `code` or `kind`";
+                return Err(syn::Error::new(name.span(), message));
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        Ok(())
+    }
+}
+
+/// A struct, or a variant of an enum.
+struct Variant<'a> {
+    /// Path of the struct or variant in patterns, e.g. `Self::NotFound`.
+    path: TokenStream2,
+    /// Span of the name of the struct or variant.
+    span: Span,
+    fields: &'a Fields,
+    attrs: Attrs,
+    /// Index of the field returned by `source`.
+    source: Option<usize>,
+    /// Index of the field converted from by a `From` impl.
+    from: Option<usize>,
+}
+
+impl<'a> Variant<'a> {
+    fn new(
+        path: TokenStream2,
+        span: Span,
+        fields: &'a Fields,
+        attrs: &[Attribute],
+    ) -> syn::Result<Self> {
+        let mut source = None;
+        let mut from = None;
+        for (i, field) in fields.iter().enumerate() {
+            let is_from = field.attrs.iter().any(|v| v.path().is_ident("from"));
+            let is_source = is_from
+                || field.attrs.iter().any(|v| v.path().is_ident("source"))
+                || field.ident.as_ref().is_some_and(|v| v == "source");
+            if is_source && source.replace(i).is_some() {
+                return Err(syn::Error::new(field.span(), "duplicate source field"));
+            }
+            if is_from {
+                from = Some(i);
+            }
+        }
+
+        let variant = Self {
+            path,
+            span,
+            fields,
+            attrs: Attrs::parse(attrs)?,
+            source,
+            from,
+        };
+        if from.is_some() && fields.len() != 1 {
+            let message = "`#[from]` requires the field to be the only one";
+            return Err(syn::Error::new(fields.span(), message));
+        }
+        if variant.attrs.transparent && fields.len() != 1 {
+            let message = "`#[error(transparent)]` requires exactly one field";
+            return Err(syn::Error::new(fields.span(), message));
+        }
+        Ok(variant)
+    }
+
+    /// Returns the name a field is bound to in [Variant::pattern], e.g. `_0` for the first
+    /// field of a tuple.
+    fn binding(&self, i: usize) -> Ident {
+        match self.fields.iter().nth(i).and_then(|v| v.ident.as_ref()) {
+            Some(ident) => ident.clone(),
+            None => format_ident!("_{}", i),
+        }
+    }
+
+    /// Returns a pattern binding every field, see [Variant::binding].
+    fn pattern(&self) -> TokenStream2 {
+        let path = &self.path;
+        let bindings = (0..self.fields.len()).map(|i| self.binding(i));
+        match self.fields {
+            Fields::Named(_) => quote!(#path { #(#bindings),* }),
+            Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+            Fields::Unit => quote!(#path),
+        }
+    }
+
+    fn display(&self) -> syn::Result<TokenStream2> {
+        let pattern = self.pattern();
+        if self.attrs.transparent {
+            let field = self.binding(0);
+            return Ok(quote!(#pattern => ::std::fmt::Display::fmt(#field, f)));
+        }
+
+        let Some(ref message) = self.attrs.message else {
+            let message = "missing `#[error(\"...\")]` message or `#[error(transparent)]`";
+            return Err(syn::Error::new(self.span, message));
+        };
+        let message = match self.fields {
+            Fields::Unnamed(_) => positional(message),
+            _ => message.clone(),
+        };
+        Ok(quote!(#pattern => ::std::write!(f, #message)))
+    }
+
+    fn source(&self) -> Option<TokenStream2> {
+        let pattern = self.pattern();
+        if self.attrs.transparent {
+            let field = self.binding(0);
+            return Some(quote! {
+                #pattern => ::std::error::Error::source(#field.as_dyn_error())
+            });
+        }
+
+        let field = self.binding(self.source?);
+        Some(quote!(#pattern => ::std::option::Option::Some(#field.as_dyn_error())))
+    }
+
+    /// Returns the `From` impl for the field marked with `#[from]`.
+    fn from(&self, input: &DeriveInput) -> Option<TokenStream2> {
+        let field = self.fields.iter().nth(self.from?)?;
+        let ty = &field.ty;
+        let path = &self.path;
+        let member = match field.ident {
+            Some(ref ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(0.into()),
+        };
+        let ident = &input.ident;
+        let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+        Some(quote! {
+            impl #impl_generics ::std::convert::From<#ty> for #ident #ty_generics #where_clause {
+                fn from(source: #ty) -> Self {
+                    #path { #member: source }
+                }
+            }
+        })
+    }
+}
+
+/// Replaces positional arguments such as `{0}` in a format string by the bindings of
+/// tuple fields, e.g. `{_0}`.
+fn positional(message: &LitStr) -> LitStr {
+    let value = message.value();
+    let mut s = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        s.push(c);
+        if c != '{' {
+            continue;
+        }
+        match chars.peek() {
+            Some('{') => s.extend(chars.next()),
+            Some(v) if v.is_ascii_digit() => s.push('_'),
+            _ => {}
+        }
+    }
+    LitStr::new(&s, message.span())
+}
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let attrs = Attrs::parse(&input.attrs)?;
+    let variants = match input.data {
+        Data::Struct(ref v) => vec![Variant::new(
+            quote!(Self),
+            input.ident.span(),
+            &v.fields,
+            &input.attrs,
+        )?],
+        Data::Enum(ref v) => {
+            if attrs.message.is_some() || attrs.transparent {
+                let message = "a message can only be given to the variants of an enum";
+                return Err(syn::Error::new(input.ident.span(), message));
+            }
+            v.variants
+                .iter()
+                .map(|v| {
+                    let ident = &v.ident;
+                    Variant::new(quote!(Self::#ident), ident.span(), &v.fields, &v.attrs)
+                })
+                .collect::<syn::Result<_>>()?
+        }
+        Data::Union(ref v) => {
+            let message = "`#[derive(conerror::Error)]` can only be used on structs and enums";
+            return Err(syn::Error::new(v.union_token.span(), message));
+        }
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let self_ty: Type = syn::parse_quote!(#ident #ty_generics);
+    let mut predicates = where_clause
+        .map(|v| v.predicates.clone())
+        .unwrap_or_default();
+    predicates.push(syn::parse_quote!(#self_ty: ::std::fmt::Debug));
+    let display = variants
+        .iter()
+        .map(Variant::display)
+        .collect::<syn::Result<Vec<_>>>()?;
+    let source = variants.iter().filter_map(Variant::source);
+    let from = variants.iter().filter_map(|v| v.from(&input));
+
+    // A variant without its own code or kind gets the one of the enum, if any.
+    let codes: Vec<_> = variants
+        .iter()
+        .filter_map(|v| {
+            let code = v.attrs.code.as_ref().or(attrs.code.as_ref())?;
+            let path = &v.path;
+            Some(quote!(#path { .. } => ::std::option::Option::Some(#code)))
+        })
+        .collect();
+    let kinds: Vec<_> = variants
+        .iter()
+        .filter_map(|v| {
+            let kind = v.attrs.kind.as_ref().or(attrs.kind.as_ref())?;
+            let path = &v.path;
+            Some(quote!(#path { .. } => ::std::option::Option::Some(#kind)))
+        })
+        .collect();
+    // Inherent methods are only added if asked for, since the type may have its own.
+    let coded = if codes.is_empty() && kinds.is_empty() {
+        quote! {
+            fn code(&self) -> ::std::option::Option<&'static str> {
+                ::std::option::Option::None
+            }
+
+            fn kind(&self) -> ::std::option::Option<conerror::ErrorKind> {
+                ::std::option::Option::None
+            }
+        }
+    } else {
+        quote! {
+            fn code(&self) -> ::std::option::Option<&'static str> {
+                <#self_ty>::code(self)
+            }
+
+            fn kind(&self) -> ::std::option::Option<conerror::ErrorKind> {
+                <#self_ty>::kind(self)
+            }
+        }
+    };
+    let methods = (!codes.is_empty() || !kinds.is_empty()).then(|| {
+        quote! {
+            impl #impl_generics #self_ty #where_clause {
+                /// Returns the error code given by `#[error(code = ...)]`.
+                #[allow(unreachable_patterns)]
+                pub fn code(&self) -> ::std::option::Option<&'static str> {
+                    match self {
+                        #(#codes,)*
+                        _ => ::std::option::Option::None,
+                    }
+                }
+
+                /// Returns the kind given by `#[error(kind = ...)]`.
+                #[allow(unreachable_patterns)]
+                pub fn kind(&self) -> ::std::option::Option<conerror::ErrorKind> {
+                    match self {
+                        #(#kinds,)*
+                        _ => ::std::option::Option::None,
+                    }
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::std::fmt::Display for #self_ty #where_clause {
+            #[allow(unused_variables)]
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match self {
+                    #(#display,)*
+                }
+            }
+        }
+
+        impl #impl_generics ::std::error::Error for #self_ty where #predicates {
+            #[allow(unused_variables, unreachable_patterns)]
+            fn source(&self) -> ::std::option::Option<&(dyn ::std::error::Error + 'static)> {
+                #[allow(unused_imports)]
+                use conerror::__private::AsDynError as _;
+                match self {
+                    #(#source,)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+        }
+
+        #(#from)*
+
+        #methods
+
+        impl #impl_generics conerror::__private::Coded for #self_ty #where_clause {
+            #coded
+        }
+
+        impl #impl_generics ::std::convert::From<#self_ty> for conerror::Error
+        where
+            #self_ty: conerror::__private::ErrorTrait + 'static,
+            #predicates
+        {
+            #[track_caller]
+            fn from(error: #self_ty) -> Self {
+                conerror::__private::from_coded(error)
+            }
+        }
+    })
+}
@@ -1,22 +1,197 @@
 use proc_macro::TokenStream;
 
-use quote::quote;
-use syn::parse::discouraged::Speculative;
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::meta::ParseNestedMeta;
 use syn::parse::{Parse, ParseStream};
 use syn::spanned::Spanned;
-use syn::visit_mut::{visit_expr_try_mut, visit_impl_item_fn_mut, VisitMut};
-use syn::{parse_macro_input, parse_quote_spanned, ExprTry, ImplItemFn, ItemFn, ItemImpl, Type};
+use syn::visit_mut::{
+    visit_block_mut, visit_expr_async_mut, visit_expr_closure_mut, visit_expr_mut,
+    visit_expr_try_mut, visit_stmt_mut, VisitMut,
+};
+use syn::{
+    parse_macro_input, parse_quote, parse_quote_spanned, Attribute, Block, Expr, ExprAsync,
+    ExprClosure, ExprTry, FnArg, GenericArgument, Ident, ImplItemFn, ItemFn, ItemImpl, ItemTrait,
+    LitStr, Macro, Meta, Pat, Path, PathArguments, ReturnType, Signature, Stmt, Token, TraitItemFn,
+    Type,
+};
+
+mod derive;
 
 #[proc_macro_attribute]
-pub fn conerror(_: TokenStream, input: TokenStream) -> TokenStream {
-    match parse_macro_input!(input as Item) {
+pub fn conerror(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut options = Options::default();
+    let parser = syn::meta::parser(|meta| options.parse_meta(meta));
+    parse_macro_input!(args with parser);
+    if options.skip {
+        let message = "`skip` can only be used on methods, statements and expressions \
+            inside a `#[conerror]` item";
+        return syn::Error::new(Span::call_site(), message)
+            .to_compile_error()
+            .into();
+    }
+
+    let item = parse_macro_input!(input as Item);
+    if !matches!(item, Item::Fn(_)) {
+        if let Some(e) = options.fn_only_error() {
+            return e.to_compile_error().into();
+        }
+    }
+
+    match item {
         Item::Fn(mut f) => {
-            MapErr::new(None, Some(f.sig.ident.to_string())).visit_item_fn_mut(&mut f);
-            quote!(#f).into()
+            if let Some(receiver) = f.sig.receiver() {
+                let message =
+                    "`#[conerror]` on a method requires `#[conerror]` on the impl block, \
+                    which knows the type name of the method";
+                return syn::Error::new(receiver.span(), message)
+                    .to_compile_error()
+                    .into();
+            }
+            if let Some(span) = find_self(f.sig.to_token_stream()) {
+                let message =
+                    "`#[conerror]` on an associated function requires `#[conerror]` on the impl \
+                    block, which knows the type name of the function";
+                return syn::Error::new(span, message).to_compile_error().into();
+            }
+
+            let active = may_return_result(&f.sig.output);
+            let mut map_err = MapErr::new(quote!(module_path!()));
+            map_err.visit_fn(&f.sig, &mut f.block, &options, active);
+            map_err.finish(quote!(#f))
         }
         Item::Impl(mut i) => {
-            MapErr::new(Some(i.self_ty.clone()), None).visit_item_impl_mut(&mut i);
-            quote!(#i).into()
+            let self_ty = tokens_to_string(&i.self_ty);
+            let scope = match i.trait_ {
+                // Methods of a trait impl are named like `<Type as Trait>::method`.
+                Some((_, ref path, _)) => format!("<{} as {}>", self_ty, tokens_to_string(path)),
+                None => self_ty,
+            };
+            let mut map_err = MapErr::new(quote!(concat!(module_path!(), "::", #scope)));
+            map_err.visit_item_impl_mut(&mut i);
+            map_err.finish(quote!(#i))
+        }
+        Item::Trait(mut t) => {
+            let scope = t.ident.to_string();
+            let mut map_err = MapErr::new(quote!(concat!(module_path!(), "::", #scope)));
+            map_err.visit_item_trait_mut(&mut t);
+            map_err.finish(quote!(#t))
+        }
+    }
+}
+
+#[proc_macro_derive(Error, attributes(error, source, from))]
+pub fn derive_error(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    derive::derive(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Options of the `#[conerror(...)]` attribute.
+#[derive(Default)]
+struct Options {
+    /// Leaves the method, statement or expression untouched.
+    skip: bool,
+    /// Context added to errors leaving the function, which may refer to its arguments.
+    ///
+    /// It is formatted when the error leaves, so the arguments it refers to must not have been
+    /// moved by the body.
+    context: Option<LitStr>,
+    /// Arguments whose `Debug` values are attached to the location recorded at each `?`.
+    args: Vec<Ident>,
+    /// Code set on errors leaving the function that have none.
+    code: Option<Expr>,
+    /// Kind set on errors leaving the function that have none.
+    kind: Option<Expr>,
+}
+
+impl Options {
+    fn parse_meta(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("skip") {
+            self.skip = true;
+            Ok(())
+        } else if meta.path.is_ident("context") {
+            self.context = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("code") {
+            self.code = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("kind") {
+            self.kind = Some(error_kind(meta.value()?.parse()?));
+            Ok(())
+        } else if meta.path.is_ident("args") {
+            meta.parse_nested_meta(|meta| match meta.path.get_ident() {
+                Some(ident) if meta.input.is_empty() || meta.input.peek(Token![,]) => {
+                    self.args.push(ident.clone());
+                    Ok(())
+                }
+                _ => Err(meta.error("expected an argument name")),
+            })
+        } else {
+            Err(meta.error(
+                "unsupported conerror option, expected `skip`, `context`, `args`, `code` or `kind`",
+            ))
+        }
+    }
+
+    /// Returns an error if an option that is only valid on functions is present.
+    fn fn_only_error(&self) -> Option<syn::Error> {
+        let (span, name) = if let Some(ref v) = self.context {
+            (v.span(), "context")
+        } else if let Some(v) = self.args.first() {
+            (v.span(), "args")
+        } else if let Some(ref v) = self.code {
+            (v.span(), "code")
+        } else if let Some(ref v) = self.kind {
+            (v.span(), "kind")
+        } else {
+            return None;
+        };
+        let message = format!("`{}` can only be used on functions and methods", name);
+        Some(syn::Error::new(span, message))
+    }
+
+    /// Returns the expression applied to `err` leaving the function, e.g. `err.context(...)`.
+    fn map_err(&self) -> Option<TokenStream2> {
+        if self.context.is_none() && self.code.is_none() && self.kind.is_none() {
+            return None;
+        }
+
+        let mut err = quote!(err);
+        if let Some(ref context) = self.context {
+            err = quote!(#err.context(format!(#context)));
+        }
+        if let Some(ref code) = self.code {
+            err = quote!(conerror::__private::or_code(#err, #code));
+        }
+        if let Some(ref kind) = self.kind {
+            err = quote!(conerror::__private::or_kind(#err, #kind));
+        }
+        Some(err)
+    }
+
+    /// Returns the expression applied to `err` leaving the function through `?`,
+    /// after the location of the `?` was recorded.
+    ///
+    /// `values` are the expressions giving the `Debug` strings of [Options::args].
+    fn map_err_try(&self, values: &[TokenStream2]) -> Option<TokenStream2> {
+        if self.args.is_empty() {
+            return self.map_err();
+        }
+
+        let names = self.args.iter().map(|v| v.to_string());
+        let err = quote!(err #(.attach(#names, #values))*);
+        match self.map_err() {
+            Some(map_err) => Some(quote!({ let err = #err; #map_err })),
+            None => Some(err),
+        }
+    }
+
+    fn parse_attr(&mut self, attr: &Attribute) -> syn::Result<()> {
+        match attr.meta {
+            Meta::Path(_) => Ok(()),
+            _ => attr.parse_nested_meta(|meta| self.parse_meta(meta)),
         }
     }
 }
@@ -24,67 +199,503 @@ pub fn conerror(_: TokenStream, input: TokenStream) -> TokenStream {
 enum Item {
     Fn(ItemFn),
     Impl(ItemImpl),
+    Trait(ItemTrait),
 }
 
 impl Parse for Item {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let ahead = input.fork();
-        match ahead.parse::<ItemFn>() {
-            Ok(v) => {
-                input.advance_to(&ahead);
-                Ok(Item::Fn(v))
-            }
-            Err(e) => match input.parse::<ItemImpl>() {
-                Ok(v) => Ok(Item::Impl(v)),
-                Err(mut e1) => {
-                    e1.combine(e);
-                    Err(e1)
-                }
-            },
+        match input.parse::<syn::Item>()? {
+            syn::Item::Fn(v) => Ok(Item::Fn(v)),
+            syn::Item::Impl(v) => Ok(Item::Impl(v)),
+            syn::Item::Trait(v) => Ok(Item::Trait(v)),
+            v => Err(syn::Error::new(
+                v.span(),
+                "`#[conerror]` can only be used on functions, impl blocks and traits",
+            )),
         }
     }
 }
 
+/// Rewrites every `?` of the function body into a call to `conerror::Error::chain`.
+///
+/// Statements and expressions marked with `#[conerror(skip)]` are left untouched.
+/// Closures, async blocks and nested functions get their own frame name, e.g.
+/// `run::{closure}`, since a `?` inside them does not leave the enclosing function.
 struct MapErr {
-    self_ty: Option<Box<Type>>,
-    ident: Option<String>,
+    /// Expression evaluating to the module of the frame.
+    module: TokenStream2,
+    /// Name of the frame, e.g. `run` or `run::{closure}`.
+    func: String,
+    /// Whether `?` in the current scope may operate on a `Result`.
+    active: bool,
+    /// Expression applied to errors leaving the current function, see [Options::map_err].
+    map_err: Option<TokenStream2>,
+    /// Expression applied to errors leaving the current function through `?`,
+    /// see [Options::map_err_try].
+    map_err_try: Option<TokenStream2>,
+    /// Errors found in `#[conerror(...)]` attributes of the body.
+    errors: Option<syn::Error>,
 }
 
 impl MapErr {
-    fn new(self_ty: Option<Box<Type>>, ident: Option<String>) -> Self {
-        Self { self_ty, ident }
+    fn new(module: TokenStream2) -> Self {
+        Self {
+            module,
+            func: String::new(),
+            active: false,
+            map_err: None,
+            map_err_try: None,
+            errors: None,
+        }
+    }
+
+    /// Visits the body of an annotated function or method, rewriting its `?` if `active`.
+    fn visit_fn(&mut self, sig: &Signature, block: &mut Block, options: &Options, active: bool) {
+        self.func = sig.ident.to_string();
+        self.active = active;
+        self.map_err = options.map_err();
+        // Arguments that may be moved by the body are formatted before it runs.
+        let mut prelude: Vec<Stmt> = Vec::new();
+        let mut values = Vec::new();
+        for arg in &options.args {
+            match find_arg(sig, arg) {
+                Some(true) => values.push(quote!(format!("{:?}", #arg))),
+                Some(false) => {
+                    let binding = format_ident!("__conerror_{}", arg, span = Span::mixed_site());
+                    prelude.push(parse_quote!(let #binding = format!("{:?}", #arg);));
+                    values.push(quote!(#binding.clone()));
+                }
+                None => {
+                    let message = format!("`{}` is not an argument of `{}`", arg, sig.ident);
+                    self.push_error(syn::Error::new(arg.span(), message));
+                    values.push(quote!(String::new()));
+                }
+            }
+        }
+        self.map_err_try = options.map_err_try(&values);
+        self.visit_block_mut(block);
+        self.map_err_try = None;
+        block.stmts.splice(0..0, prelude);
+
+        // The value of the body leaves the function as well.
+        if let Some(map_err) = self.map_err.take() {
+            if let Some(Stmt::Expr(expr, None)) = block.stmts.last_mut() {
+                *expr = map_err_expr(expr, &map_err);
+            }
+        }
+    }
+
+    /// Removes the `#[conerror]` attributes of a statement or expression,
+    /// returning whether it is marked with `skip`.
+    fn take_skip(&mut self, attrs: &mut Vec<Attribute>) -> bool {
+        let Some(options) = self.take_options(attrs) else {
+            return false;
+        };
+        if let Some(e) = options.fn_only_error() {
+            self.push_error(e);
+        }
+        options.skip
+    }
+
+    /// Returns the options of a method of an impl block or trait, and whether its `?` are
+    /// rewritten.
+    ///
+    /// A method marked with `#[conerror]` is treated like an annotated function. Others are only
+    /// instrumented if they return a `conerror::Result`, so that e.g. a `fmt::Result` method
+    /// keeps compiling.
+    fn method_options(&mut self, attrs: &mut Vec<Attribute>, sig: &Signature) -> (Options, bool) {
+        match self.take_options(attrs) {
+            Some(options) => (options, may_return_result(&sig.output)),
+            None => (Options::default(), returns_conerror(&sig.output)),
+        }
+    }
+
+    /// Removes the `#[conerror]` attributes, returning their options if any was present.
+    fn take_options(&mut self, attrs: &mut Vec<Attribute>) -> Option<Options> {
+        let mut options = None;
+        attrs.retain(|attr| {
+            if !attr.path().is_ident("conerror") {
+                return true;
+            }
+            let options = options.get_or_insert_with(Options::default);
+            if let Err(e) = options.parse_attr(attr) {
+                self.push_error(e);
+            }
+            false
+        });
+        options
+    }
+
+    fn push_error(&mut self, error: syn::Error) {
+        match self.errors {
+            Some(ref mut v) => v.combine(error),
+            None => self.errors = Some(error),
+        }
+    }
+
+    /// Appends the collected errors to the output as `compile_error!` invocations.
+    fn finish(self, output: TokenStream2) -> TokenStream {
+        let errors = self.errors.map(|v| v.to_compile_error());
+        quote!(#output #errors).into()
+    }
+
+    /// Visits a nested scope with the given frame name, restoring the current frame afterwards.
+    fn visit_nested(&mut self, func: String, active: bool, f: impl FnOnce(&mut Self)) {
+        let func = std::mem::replace(&mut self.func, func);
+        let active = std::mem::replace(&mut self.active, active);
+        let map_err = self.map_err.take();
+        let map_err_try = self.map_err_try.take();
+        f(self);
+        self.func = func;
+        self.active = active;
+        self.map_err = map_err;
+        self.map_err_try = map_err_try;
     }
 }
 
 impl VisitMut for MapErr {
-    fn visit_expr_try_mut(&mut self, i: &mut ExprTry) {
-        let ident = self.ident.as_ref().unwrap();
-        let module = match self.self_ty {
-            Some(ref v) => quote!(std::any::type_name::<#v>()),
-            None => quote!(module_path!()),
+    fn visit_stmt_mut(&mut self, i: &mut Stmt) {
+        let attrs = match i {
+            Stmt::Local(v) => Some(&mut v.attrs),
+            Stmt::Macro(v) => Some(&mut v.attrs),
+            _ => None,
         };
+        if let Some(attrs) = attrs {
+            if self.take_skip(attrs) {
+                return;
+            }
+        }
+        visit_stmt_mut(self, i);
+    }
+
+    fn visit_expr_mut(&mut self, i: &mut Expr) {
+        if let Some(attrs) = expr_attrs_mut(i) {
+            if self.take_skip(attrs) {
+                return;
+            }
+        }
+        visit_expr_mut(self, i);
+
+        if let (Some(map_err), Expr::Return(v)) = (&self.map_err, &*i) {
+            if let Some(ref expr) = v.expr {
+                let expr = map_err_call(expr, map_err);
+                // The statement allows the lint on an expression that diverges, e.g. `return loop {}`.
+                *i = parse_quote_spanned! {v.span() =>
+                    { #[allow(unreachable_code)] return #expr; }
+                };
+            }
+        }
+    }
+
+    fn visit_expr_try_mut(&mut self, i: &mut ExprTry) {
+        visit_expr_try_mut(self, i);
+        if !self.active {
+            return;
+        }
+
+        let func = &self.func;
+        let module = &self.module;
         let expr = &i.expr;
+        let location = quote_spanned! {i.question_token.span() =>
+            file!(), line!(), column!()
+        };
+        let map_err = match self.map_err_try {
+            Some(ref map_err) => map_err.clone(),
+            None => quote!(err),
+        };
         *i.expr = parse_quote_spanned! {expr.span() =>
-            #expr.map_err(|err| conerror::Error::chain(err, file!(), line!(), #ident, #module))
+            conerror::__private::Chain::chain(
+                #expr, #location, #func, #module,
+                |source| conerror::__code!(source),
+                |err| #map_err,
+            )
         };
-        visit_expr_try_mut(self, i);
+    }
+
+    fn visit_macro_mut(&mut self, i: &mut Macro) {
+        if self.func.is_empty() || !is_conerror_macro(&i.path) {
+            return;
+        }
+
+        let func = &self.func;
+        let module = &self.module;
+        let tokens = &i.tokens;
+        i.tokens = quote!(@frame(#func, #module) #tokens);
+    }
+
+    fn visit_expr_closure_mut(&mut self, i: &mut ExprClosure) {
+        let func = format!("{}::{{closure}}", self.func);
+        let active = match i.output {
+            // Closures without a return type may still return a `conerror::Result`.
+            ReturnType::Default => tail_error(&i.body).map_or(true, is_conerror_error),
+            _ => returns_conerror(&i.output),
+        };
+        self.visit_nested(func, active, |v| visit_expr_closure_mut(v, i));
+    }
+
+    fn visit_expr_async_mut(&mut self, i: &mut ExprAsync) {
+        let func = format!("{}::{{async block}}", self.func);
+        let active = block_error(&i.block).map_or(true, is_conerror_error);
+        self.visit_nested(func, active, |v| visit_expr_async_mut(v, i));
+    }
+
+    fn visit_item_mut(&mut self, i: &mut syn::Item) {
+        // Other nested items, such as impl blocks, are not part of the function.
+        if let syn::Item::Fn(f) = i {
+            self.visit_item_fn_mut(f);
+        }
+    }
+
+    fn visit_item_fn_mut(&mut self, i: &mut ItemFn) {
+        let func = format!("{}::{}", self.func, i.sig.ident);
+        let active = returns_conerror(&i.sig.output);
+        self.visit_nested(func, active, |v| visit_block_mut(v, &mut i.block));
     }
 
     fn visit_impl_item_fn_mut(&mut self, i: &mut ImplItemFn) {
-        let mut indices = vec![];
-        for (i, attr) in i.attrs.iter().enumerate() {
-            if attr.path().is_ident("conerror") {
-                indices.push(i);
-            }
+        let (options, active) = self.method_options(&mut i.attrs, &i.sig);
+        if !options.skip {
+            self.visit_fn(&i.sig, &mut i.block, &options, active);
         }
-        if indices.is_empty() {
-            return;
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, i: &mut TraitItemFn) {
+        let (options, active) = self.method_options(&mut i.attrs, &i.sig);
+        if let (false, Some(block)) = (options.skip, &mut i.default) {
+            self.visit_fn(&i.sig, block, &options, active);
+        }
+    }
+}
+
+/// Applies the expression of [Options::map_err] to the error of a `Result` leaving the function
+/// as the last expression of its body.
+///
+/// A body that diverges, such as a `loop` without `break`, would otherwise make the call warn
+/// about unreachable code.
+fn map_err_expr(expr: &Expr, map_err: &TokenStream2) -> Expr {
+    let expr = map_err_call(expr, map_err);
+    parse_quote_spanned! {expr.span() =>
+        #[allow(unreachable_code)]
+        { #expr }
+    }
+}
+
+fn map_err_call(expr: &Expr, map_err: &TokenStream2) -> Expr {
+    parse_quote_spanned! {expr.span() =>
+        conerror::__private::map_err(#expr, |err| #map_err)
+    }
+}
+
+/// Resolves a bare variant like `NotFound` or `Custom("...")` to `conerror::ErrorKind`,
+/// leaving any other expression as is.
+fn error_kind(expr: Expr) -> Expr {
+    let path = match expr {
+        Expr::Path(ref v) => &v.path,
+        Expr::Call(ref v) => match *v.func {
+            Expr::Path(ref v) => &v.path,
+            _ => return expr,
+        },
+        _ => return expr,
+    };
+    if path.get_ident().is_none() {
+        return expr;
+    }
+    parse_quote_spanned!(expr.span() => conerror::ErrorKind::#expr)
+}
+
+/// Looks up the argument named `ident`, or `self` for `self`, returning whether it is a shared
+/// reference or a primitive, which cannot be moved away by the body and is formatted only when
+/// there is an error.
+fn find_arg(sig: &Signature, ident: &Ident) -> Option<bool> {
+    sig.inputs.iter().find_map(|v| match v {
+        FnArg::Receiver(v) if ident == "self" => {
+            Some(v.reference.is_some() && v.mutability.is_none())
+        }
+        FnArg::Typed(v) if matches!(&*v.pat, Pat::Ident(v) if v.ident == *ident) => {
+            Some(is_borrowed_or_primitive(&v.ty))
+        }
+        _ => None,
+    })
+}
+
+fn is_borrowed_or_primitive(ty: &Type) -> bool {
+    const PRIMITIVES: &[&str] = &[
+        "bool", "char", "f32", "f64", "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16",
+        "u32", "u64", "u128", "usize",
+    ];
+    match ty {
+        Type::Reference(v) => v.mutability.is_none(),
+        Type::Path(v) => v
+            .path
+            .get_ident()
+            .is_some_and(|v| PRIMITIVES.iter().any(|p| v == p)),
+        Type::Group(v) => is_borrowed_or_primitive(&v.elem),
+        Type::Paren(v) => is_borrowed_or_primitive(&v.elem),
+        _ => false,
+    }
+}
+
+/// Returns `true` for `conerror::conerr!`, `conerror::bail!`, `conerror::ensure!` and `conerr!`,
+/// which are given the current frame instead of looking it up at runtime.
+fn is_conerror_macro(path: &Path) -> bool {
+    let mut segments = path.segments.iter().map(|v| &v.ident);
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(name), None, None) => name == "conerr",
+        (Some(krate), Some(name), None) => {
+            krate == "conerror" && (name == "conerr" || name == "bail" || name == "ensure")
         }
+        _ => false,
+    }
+}
 
-        for idx in indices {
-            i.attrs.remove(idx);
+/// Returns the attributes of an expression.
+fn expr_attrs_mut(expr: &mut Expr) -> Option<&mut Vec<Attribute>> {
+    macro_rules! attrs {
+        ($($variant:ident),*) => {
+            match expr {
+                $(Expr::$variant(v) => Some(&mut v.attrs),)*
+                _ => None,
+            }
+        };
+    }
+
+    attrs!(
+        Array, Assign, Async, Await, Binary, Block, Break, Call, Cast, Closure, Const, Continue,
+        Field, ForLoop, Group, If, Index, Infer, Let, Lit, Loop, Macro, Match, MethodCall, Paren,
+        Path, Range, RawAddr, Reference, Repeat, Return, Struct, Try, TryBlock, Tuple, Unary,
+        Unsafe, While, Yield
+    )
+}
+
+/// Returns `false` if the return type is obviously not a `Result`, e.g. `Option<T>` or `()`.
+fn may_return_result(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    match &**ty {
+        Type::Path(v) => v.path.segments.last().is_some_and(|v| v.ident != "Option"),
+        Type::Infer(_) | Type::Group(_) | Type::Paren(_) | Type::Macro(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the return type is a `Result` whose error is a `conerror::Error`:
+/// `conerror::Result<T>`, `Result<T>` or `Result<T, Error>`.
+///
+/// Unlike [may_return_result], this is used where the function is not annotated itself,
+/// so that e.g. an `io::Result` or `fmt::Result` is left untouched.
+fn returns_conerror(output: &ReturnType) -> bool {
+    let ReturnType::Type(_, ty) = output else {
+        return false;
+    };
+    is_conerror_result(ty)
+}
+
+fn is_conerror_result(ty: &Type) -> bool {
+    let path = match ty {
+        Type::Path(v) if v.qself.is_none() => &v.path,
+        Type::Group(v) => return is_conerror_result(&v.elem),
+        Type::Paren(v) => return is_conerror_result(&v.elem),
+        Type::Infer(_) => return true,
+        _ => return false,
+    };
+    let segments: Vec<_> = path.segments.iter().map(|v| v.ident.to_string()).collect();
+    let Some(last) = path.segments.last() else {
+        return false;
+    };
+    let PathArguments::AngleBracketed(ref args) = last.arguments else {
+        return false;
+    };
+    let types: Vec<_> = args
+        .args
+        .iter()
+        .filter_map(|v| match v {
+            GenericArgument::Type(v) => Some(v),
+            _ => None,
+        })
+        .collect();
+    match (segments.as_slice(), types.as_slice()) {
+        ([krate, name], [_]) => krate == "conerror" && name == "Result",
+        ([name], [_]) => name == "Result",
+        ([.., name], [_, error]) if name == "Result" => is_conerror_error(error),
+        _ => false,
+    }
+}
+
+/// Returns the error type of the value of a closure or block ending in `Ok::<T, E>(...)` or
+/// `Err::<T, E>(...)`, the usual way to name the error of a closure without a return type.
+fn tail_error(expr: &Expr) -> Option<&Type> {
+    match expr {
+        Expr::Call(v) => {
+            let Expr::Path(ref f) = *v.func else {
+                return None;
+            };
+            let last = f.path.segments.last()?;
+            if last.ident != "Ok" && last.ident != "Err" {
+                return None;
+            }
+            let PathArguments::AngleBracketed(ref args) = last.arguments else {
+                return None;
+            };
+            match args.args.iter().nth(1)? {
+                GenericArgument::Type(v) => Some(v),
+                _ => None,
+            }
+        }
+        Expr::Async(v) => block_error(&v.block),
+        Expr::Block(v) => block_error(&v.block),
+        Expr::Unsafe(v) => block_error(&v.block),
+        Expr::Group(v) => tail_error(&v.expr),
+        Expr::Paren(v) => tail_error(&v.expr),
+        _ => None,
+    }
+}
+
+fn block_error(block: &Block) -> Option<&Type> {
+    match block.stmts.last()? {
+        Stmt::Expr(v, None) => tail_error(v),
+        _ => None,
+    }
+}
+
+/// Returns `true` for `Error`, `conerror::Error` and `_`.
+fn is_conerror_error(ty: &Type) -> bool {
+    let Type::Path(v) = ty else {
+        return matches!(ty, Type::Infer(_));
+    };
+    let mut segments = v.path.segments.iter().map(|v| &v.ident);
+    match (segments.next(), segments.next(), segments.next()) {
+        (Some(name), None, None) => name == "Error",
+        (Some(krate), Some(name), None) => krate == "conerror" && name == "Error",
+        _ => false,
+    }
+}
+
+/// Returns the span of the first `Self` in the tokens.
+fn find_self(tokens: TokenStream2) -> Option<Span> {
+    tokens.into_iter().find_map(|v| match v {
+        TokenTree::Ident(v) if v == "Self" => Some(v.span()),
+        TokenTree::Group(v) => find_self(v.stream()),
+        _ => None,
+    })
+}
+
+/// Renders tokens the way they are written, without the spaces inserted by `quote`.
+fn tokens_to_string(tokens: &impl ToTokens) -> String {
+    let s = tokens.to_token_stream().to_string();
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ' ' {
+            let prev = out.chars().last();
+            let next = chars.peek().copied();
+            if matches!(prev, Some('<' | ':' | '&' | '('))
+                || matches!(next, Some('<' | '>' | ':' | ',' | ')'))
+            {
+                continue;
+            }
         }
-        self.ident = Some(i.sig.ident.to_string());
-        visit_impl_item_fn_mut(self, i);
+        out.push(c);
     }
+    out
 }
@@ -0,0 +1,240 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+
+pub use crate::inner::ErrorTrait;
+use crate::{Error, ErrorKind};
+
+/// Adds location information to the operand of a `?` rewritten by `#[conerror]`.
+///
+/// `Option` is passed through untouched.
+#[diagnostic::on_unimplemented(
+    message = "`#[conerror]` cannot add location information to `{Self}`",
+    label = "expected an `Option`, or a `Result` whose error implements `std::error::Error`",
+    note = "add `#[conerror(skip)]` to the statement to leave this `?` untouched"
+)]
+pub trait Chain {
+    type Output;
+    /// Error type of a `Result`.
+    type Source;
+
+    /// Chains the error with the location, sets the [Code] that `probe` returns for it,
+    /// and then applies `f` to the error.
+    #[allow(clippy::too_many_arguments)]
+    fn chain<P, F>(
+        self,
+        file: &'static str,
+        line: u32,
+        column: u32,
+        func: &'static str,
+        module: &'static str,
+        probe: P,
+        f: F,
+    ) -> Self::Output
+    where
+        P: FnOnce(&Self::Source) -> Code,
+        F: FnOnce(Error) -> Error;
+}
+
+impl<T, E> Chain for Result<T, E>
+where
+    E: ErrorTrait + 'static,
+{
+    type Output = Result<T, Error>;
+    type Source = E;
+
+    #[inline]
+    fn chain<P, F>(
+        self,
+        file: &'static str,
+        line: u32,
+        column: u32,
+        func: &'static str,
+        module: &'static str,
+        probe: P,
+        f: F,
+    ) -> Self::Output
+    where
+        P: FnOnce(&E) -> Code,
+        F: FnOnce(Error) -> Error,
+    {
+        self.map_err(|err| {
+            let code = probe(&err);
+            let error = f(code.apply(Error::chain(err, file, line, column, func, module)));
+            #[cfg(feature = "tracing")]
+            crate::trace::propagated(&error, file, line, column, func, module);
+            error
+        })
+    }
+}
+
+impl<T> Chain for Option<T> {
+    type Output = Self;
+    type Source = Infallible;
+
+    #[inline]
+    fn chain<P, F>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: u32,
+        _: &'static str,
+        _: &'static str,
+        _: P,
+        _: F,
+    ) -> Self {
+        self
+    }
+}
+
+/// Implemented by `#[derive(conerror::Error)]` to give the code and kind of an error.
+pub trait Coded {
+    fn code(&self) -> Option<&'static str>;
+
+    fn kind(&self) -> Option<ErrorKind>;
+}
+
+/// Code and kind of an error about to be wrapped in an [Error].
+#[derive(Default)]
+pub struct Code {
+    code: Option<&'static str>,
+    kind: Option<ErrorKind>,
+}
+
+impl Code {
+    /// Sets the code and kind on the [Error] wrapping the error, unless it has them already.
+    #[inline]
+    pub fn apply(self, mut err: Error) -> Error {
+        if let Some(code) = self.code {
+            err = or_code(err, code);
+        }
+        if let Some(kind) = self.kind {
+            err = or_kind(err, kind);
+        }
+        err
+    }
+}
+
+/// Wraps a reference to an error for [ProbeCoded] and [ProbePlain],
+/// which give the [Code] of errors that implement [Coded] and of any other error respectively.
+///
+/// Call `(&&Probe(&error)).code()` with both traits in scope, see [crate::__code!].
+pub struct Probe<'a, E>(pub &'a E);
+
+pub trait ProbeCoded {
+    fn code(&self) -> Code;
+}
+
+impl<E> ProbeCoded for &Probe<'_, E>
+where
+    E: Coded,
+{
+    #[inline]
+    fn code(&self) -> Code {
+        Code {
+            code: self.0.code(),
+            kind: self.0.kind(),
+        }
+    }
+}
+
+pub trait ProbePlain {
+    fn code(&self) -> Code;
+}
+
+impl<E> ProbePlain for Probe<'_, E> {
+    #[inline]
+    fn code(&self) -> Code {
+        Code::default()
+    }
+}
+
+/// Converts an error deriving `conerror::Error` into an [Error] located at the caller.
+#[track_caller]
+pub fn from_coded<E>(error: E) -> Error
+where
+    E: Coded + ErrorTrait + 'static,
+{
+    let code = Code {
+        code: error.code(),
+        kind: error.kind(),
+    };
+    code.apply(Error::caller(error))
+}
+
+/// Gives `&(dyn std::error::Error + 'static)` for the source field of a type
+/// deriving `conerror::Error`, including boxed trait objects.
+pub trait AsDynError {
+    fn as_dyn_error(&self) -> &(dyn std::error::Error + 'static);
+}
+
+impl<T> AsDynError for T
+where
+    T: std::error::Error + 'static,
+{
+    #[inline]
+    fn as_dyn_error(&self) -> &(dyn std::error::Error + 'static) {
+        self
+    }
+}
+
+impl AsDynError for dyn std::error::Error + 'static {
+    #[inline]
+    fn as_dyn_error(&self) -> &(dyn std::error::Error + 'static) {
+        self
+    }
+}
+
+impl AsDynError for dyn std::error::Error + Send + Sync + 'static {
+    #[inline]
+    fn as_dyn_error(&self) -> &(dyn std::error::Error + 'static) {
+        self
+    }
+}
+
+/// Marks an [Error] created by [crate::conerr!] from a format string as created from a message.
+#[inline]
+pub fn message(err: Error) -> Error {
+    err.mark_message()
+}
+
+/// Applies `f` to the error of a `Result` leaving a `#[conerror]` function.
+#[inline]
+pub fn map_err<T, F>(result: Result<T, Error>, f: F) -> Result<T, Error>
+where
+    F: FnOnce(Error) -> Error,
+{
+    result.map_err(f)
+}
+
+/// Sets the code of an error leaving a `#[conerror(code = ...)]` function,
+/// unless a function it called has set one.
+#[inline]
+pub fn or_code(err: Error, code: impl Into<Cow<'static, str>>) -> Error {
+    match err.code() {
+        Some(_) => err,
+        None => err.with_code(code),
+    }
+}
+
+/// Sets the kind of an error leaving a `#[conerror(kind = ...)]` function,
+/// unless a function it called has set one.
+#[inline]
+pub fn or_kind(err: Error, kind: ErrorKind) -> Error {
+    match err.kind() {
+        Some(_) => err,
+        None => err.with_kind(kind),
+    }
+}
+
+/// Splits the type name of a function item defined in a function body into the name and module
+/// of the enclosing function, skipping closures and async blocks.
+pub fn frame(name: &'static str) -> (&'static str, &'static str) {
+    let mut path = name.rsplit_once("::").map_or(name, |v| v.0);
+    while let Some(v) = path.strip_suffix("::{{closure}}") {
+        path = v;
+    }
+    match path.rsplit_once("::") {
+        Some((module, func)) => (func, module),
+        None => (path, ""),
+    }
+}
@@ -0,0 +1,155 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+use crate::AttachmentValue;
+
+/// A typed value attached to an [Error](crate::Error) under a key, see [Error::attach](crate::Error::attach).
+#[derive(Debug)]
+pub struct Attachment {
+    pub(crate) key: Cow<'static, str>,
+    pub(crate) value: Box<dyn AttachmentValue>,
+}
+
+impl Attachment {
+    /// Returns the key.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the value if it is of type `T`.
+    pub fn value<T>(&self) -> Option<&T>
+    where
+        T: 'static,
+    {
+        (*self.value).as_any().downcast_ref()
+    }
+}
+
+impl Display for Attachment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// Serializes attachments as a map, keeping numbers, booleans and strings as they are.
+#[cfg(feature = "serde")]
+pub(crate) struct Attachments<'a>(pub(crate) Vec<&'a Attachment>);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Attachments<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_map(self.0.iter().map(|v| (&v.key, Value(v))))
+    }
+}
+
+#[cfg(feature = "serde")]
+struct Value<'a>(&'a Attachment);
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Value<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        macro_rules! serialize {
+            ($($ty:ty),*) => {
+                $(
+                    if let Some(v) = self.0.value::<$ty>() {
+                        return serde::Serialize::serialize(v, serializer);
+                    }
+                )*
+            };
+        }
+
+        serialize!(bool, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+        serialize!(char, String, &'static str, Cow<'static, str>);
+        serializer.collect_str(&self.0.value)
+    }
+}
+
+/// Deserializes attachments from a map, as numbers, booleans and strings.
+#[cfg(feature = "serde")]
+pub(crate) struct OwnedAttachments(pub(crate) Vec<Attachment>);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OwnedAttachments {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = OwnedAttachments;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a map of attachments")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut attachments = Vec::new();
+                while let Some((key, value)) = map.next_entry::<String, OwnedValue>()? {
+                    attachments.push(Attachment {
+                        key: key.into(),
+                        value: value.0,
+                    });
+                }
+                Ok(OwnedAttachments(attachments))
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+/// A deserialized attachment value, which is a `bool`, `u64`, `i64`, `f64` or `String`.
+#[cfg(feature = "serde")]
+struct OwnedValue(Box<dyn AttachmentValue>);
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OwnedValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        macro_rules! visit {
+            ($($name:ident($ty:ty)),*) => {
+                $(
+                    fn $name<E>(self, v: $ty) -> Result<Self::Value, E> {
+                        Ok(OwnedValue(Box::new(v)))
+                    }
+                )*
+            };
+        }
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = OwnedValue;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a number, boolean or string")
+            }
+
+            visit!(
+                visit_bool(bool),
+                visit_i64(i64),
+                visit_u64(u64),
+                visit_f64(f64)
+            );
+            visit!(visit_string(String));
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+                Ok(OwnedValue(Box::new(v.to_string())))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{Error, ErrorTrait, Result};
+
+/// Adds context to the error of a [std::result::Result].
+///
+/// An error that is not an [Error] is converted into one located where the method was called.
+///
+/// ```
+/// use conerror::ResultExt;
+///
+/// let line = line!() + 1;
+/// let e = std::fs::read("non_exists_config.toml").context("Failed to read config");
+/// let e = e.unwrap_err();
+/// assert_eq!(e.message(), "Failed to read config: No such file or directory (os error 2)");
+/// assert_eq!(e.location().unwrap()[0].line, line);
+///
+/// #[conerror::conerror]
+/// fn read() -> conerror::Result<Vec<u8>> {
+///     Ok(std::fs::read("non_exists_config.toml").context("Failed to read config")?)
+/// }
+///
+/// let e = read().unwrap_err();
+/// let location = e.location().unwrap();
+/// assert_eq!((location.len(), location[0].func), (1, "read"));
+/// ```
+pub trait ResultExt<T> {
+    /// Adds context to the error.
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: ToString;
+
+    /// Adds context to the error, evaluated only if there is an error.
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: ToString,
+        F: FnOnce() -> C;
+
+    /// Logs the error at `level` with [Error::log], and returns it.
+    ///
+    /// ```no_run
+    /// use conerror::ResultExt;
+    ///
+    /// let config = std::fs::read("config.toml").log_err(log::Level::Warn).unwrap_or_default();
+    /// ```
+    #[cfg(feature = "log")]
+    fn log_err(self, level: log::Level) -> Result<T>;
+}
+
+impl<T, E> ResultExt<T> for std::result::Result<T, E>
+where
+    E: ErrorTrait + 'static,
+{
+    #[track_caller]
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: ToString,
+    {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::caller(e).context(context)),
+        }
+    }
+
+    #[track_caller]
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: ToString,
+        F: FnOnce() -> C,
+    {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => Err(Error::caller(e).context(f())),
+        }
+    }
+
+    #[cfg(feature = "log")]
+    #[track_caller]
+    fn log_err(self, level: log::Level) -> Result<T> {
+        match self {
+            Ok(v) => Ok(v),
+            Err(e) => {
+                let e = Error::caller(e);
+                e.log(level);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Converts a [None] into an [Error] located where the method was called.
+///
+/// ```
+/// use conerror::OptionExt;
+///
+/// let (e, line) = (None::<u8>.context("missing value").unwrap_err(), line!());
+/// assert_eq!(e.location().unwrap()[0].line, line);
+/// assert!(e.to_string().starts_with(&format!("missing value\n#0 {}:{}:", file!(), line)));
+/// ```
+pub trait OptionExt<T> {
+    /// Converts a [None] into an [Error] with the given message.
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: ToString;
+
+    /// Converts a [None] into an [Error] with the given message, evaluated only if it is [None].
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: ToString,
+        F: FnOnce() -> C;
+}
+
+impl<T> OptionExt<T> for Option<T> {
+    #[track_caller]
+    fn context<C>(self, context: C) -> Result<T>
+    where
+        C: ToString,
+    {
+        match self {
+            Some(v) => Ok(v),
+            None => Err(Error::caller(context.to_string()).mark_message()),
+        }
+    }
+
+    #[track_caller]
+    fn with_context<C, F>(self, f: F) -> Result<T>
+    where
+        C: ToString,
+        F: FnOnce() -> C,
+    {
+        match self {
+            Some(v) => Ok(v),
+            None => Err(Error::caller(f().to_string()).mark_message()),
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Configurable formatting of an [Error] and its locations.
+
+use std::fmt::{Display, Formatter};
+
+use crate::{Attachment, Error, Location, RemoteFrame};
+
+/// How an [Error] and its locations are laid out, see [TraceFormat::layout].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layout {
+    /// The message on the first line, followed by a line per location.
+    MultiLine,
+    /// The message and the locations on a single line, separated by ` | `.
+    Compact,
+}
+
+/// How the file of a location is written, see [TraceFormat::paths].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Paths {
+    /// The path given by `file!()`, which is absolute for dependencies.
+    Full,
+    /// The path relative to the crate the file belongs to, e.g. `src/lib.rs`.
+    CrateRelative,
+}
+
+/// Order in which locations are written, see [TraceFormat::order].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Order {
+    /// The location where the error occurred first.
+    InnermostFirst,
+    /// The location the error was passed up to last first.
+    OutermostFirst,
+}
+
+/// Options for writing an [Error] with [Error::format].
+///
+/// [TraceFormat::new] is the format of `Display`, and can be adjusted like this:
+///
+/// ```
+/// use conerror::format::{Order, TraceFormat};
+///
+/// let e = conerror::Error::new("not found", "src/lib.rs", 3, 5, "read", "app::db")
+///     .context("Failed to read user");
+/// let e = conerror::Error::chain(e, "src/main.rs", 8, 13, "main", "app");
+///
+/// assert_eq!(e.format(TraceFormat::new()).to_string(), e.to_string());
+/// assert_eq!(
+///     e.format(TraceFormat::compact().modules(false)).to_string(),
+///     "not found | #0 src/lib.rs:3:5 read() — Failed to read user | #1 src/main.rs:8:13 main()",
+/// );
+/// assert_eq!(
+///     e.format(TraceFormat::new().order(Order::OutermostFirst)).to_string(),
+///     "not found\n#1 src/main.rs:8:13 app::main()\n#0 src/lib.rs:3:5 app::db::read() — Failed to read user",
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TraceFormat {
+    layout: Layout,
+    paths: Paths,
+    modules: bool,
+    color: bool,
+    order: Order,
+}
+
+impl TraceFormat {
+    /// Returns the format of `Display`: multi-line, with full paths and module paths,
+    /// without colors and innermost location first.
+    pub const fn new() -> Self {
+        Self {
+            layout: Layout::MultiLine,
+            paths: Paths::Full,
+            modules: true,
+            color: false,
+            order: Order::InnermostFirst,
+        }
+    }
+
+    /// Returns [TraceFormat::new] on a single line, e.g. for log lines.
+    pub const fn compact() -> Self {
+        Self::new().layout(Layout::Compact)
+    }
+
+    /// Sets how the error and its locations are laid out.
+    pub const fn layout(mut self, layout: Layout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets how the file of a location is written.
+    pub const fn paths(mut self, paths: Paths) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Sets whether the module path is written before the function of a location.
+    pub const fn modules(mut self, modules: bool) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    /// Sets whether ANSI colors are used.
+    pub const fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the order in which locations are written.
+    ///
+    /// Locations keep their number, `#0` being where the error occurred.
+    pub const fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Writes `value` in the ANSI style `style` if colors are enabled.
+    pub(crate) fn paint(
+        &self,
+        f: &mut Formatter<'_>,
+        style: &str,
+        value: impl Display,
+    ) -> std::fmt::Result {
+        self.start(f, style)?;
+        write!(f, "{}", value)?;
+        self.end(f)
+    }
+
+    /// Starts the ANSI style `style` if colors are enabled.
+    pub(crate) fn start(&self, f: &mut Formatter<'_>, style: &str) -> std::fmt::Result {
+        if self.color {
+            write!(f, "\x1b[{}m", style)?;
+        }
+        Ok(())
+    }
+
+    /// Resets the style started by [TraceFormat::start].
+    pub(crate) fn end(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.color {
+            f.write_str("\x1b[0m")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_compact(&self) -> bool {
+        self.layout == Layout::Compact
+    }
+
+    pub(crate) fn separator(&self) -> &'static str {
+        match self.layout {
+            Layout::MultiLine => "\n",
+            Layout::Compact => " | ",
+        }
+    }
+
+    /// Writes `file:line:column`, or `file:line` if the column is unknown.
+    pub(crate) fn fmt_position(
+        &self,
+        f: &mut Formatter<'_>,
+        file: &str,
+        line: u32,
+        column: u32,
+    ) -> std::fmt::Result {
+        let file = match self.paths {
+            Paths::Full => file,
+            Paths::CrateRelative => crate_relative(file),
+        };
+        match column {
+            0 => self.paint(f, CYAN, format_args!("{}:{}", file, line)),
+            column => self.paint(f, CYAN, format_args!("{}:{}:{}", file, line, column)),
+        }
+    }
+
+    /// Writes `file:line:column module::func()`.
+    fn fmt_location(
+        &self,
+        f: &mut Formatter<'_>,
+        (file, line, column): (&str, u32, u32),
+        func: &str,
+        module: &str,
+    ) -> std::fmt::Result {
+        self.fmt_position(f, file, line, column)?;
+        if func.is_empty() {
+            return Ok(());
+        }
+
+        f.write_str(" ")?;
+        if self.modules && !module.is_empty() {
+            self.paint(f, GREEN, format_args!("{}::{}()", module, func))
+        } else {
+            self.paint(f, GREEN, format_args!("{}()", func))
+        }
+    }
+
+    /// Writes attachments as ` (key=value, key=value)`.
+    fn fmt_attachments<'a>(
+        &self,
+        f: &mut Formatter<'_>,
+        attachments: impl Iterator<Item = &'a Attachment>,
+    ) -> std::fmt::Result {
+        let mut attachments = attachments.peekable();
+        if attachments.peek().is_none() {
+            return Ok(());
+        }
+
+        f.write_str(" ")?;
+        self.start(f, DIM)?;
+        for (i, v) in attachments.enumerate() {
+            let sep = if i == 0 { "(" } else { ", " };
+            write!(f, "{}{}", sep, v)?;
+        }
+        f.write_str(")")?;
+        self.end(f)
+    }
+
+    /// Writes a location with its context, without the separator before it.
+    pub(crate) fn fmt_frame(
+        &self,
+        f: &mut Formatter<'_>,
+        error: &Error,
+        i: usize,
+        frame: Frame<'_>,
+    ) -> std::fmt::Result {
+        self.paint(f, DIM, format_args!("#{}", i))?;
+        f.write_str(" ")?;
+        match frame {
+            Frame::Local(j, location) => {
+                let position = (location.file, location.line, location.column);
+                self.fmt_location(f, position, location.func, location.module)?;
+                self.fmt_context(f, error.frame_messages(Some(j)))?;
+                self.fmt_attachments(f, error.frame_attachments(Some(j)))
+            }
+            Frame::Remote(frame) => {
+                let service = match frame.service {
+                    Some(ref v) => format!("[remote {}]", v),
+                    None => "[remote]".to_string(),
+                };
+                self.paint(f, DIM, service)?;
+                f.write_str(" ")?;
+                let position = (frame.file.as_str(), frame.line, frame.column);
+                self.fmt_location(f, position, &frame.func, &frame.module)?;
+                self.fmt_context(f, frame.context.iter().map(String::as_str))?;
+                self.fmt_attachments(f, frame.attachments.iter())
+            }
+        }
+    }
+
+    /// Writes context messages as ` — context: context`.
+    fn fmt_context<'a>(
+        &self,
+        f: &mut Formatter<'_>,
+        context: impl Iterator<Item = &'a str>,
+    ) -> std::fmt::Result {
+        for (j, c) in context.enumerate() {
+            let sep = if j == 0 { " — " } else { ": " };
+            f.write_str(sep)?;
+            self.paint(f, YELLOW, c)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the message with the context and attachments of the error itself.
+    pub(crate) fn fmt_message(&self, f: &mut Formatter<'_>, error: &Error) -> std::fmt::Result {
+        for c in error.frame_messages(None) {
+            self.paint(f, BOLD, format_args!("{}: ", c))?;
+        }
+        self.paint(f, BOLD, &error.0.source)?;
+        self.fmt_attachments(f, error.frame_attachments(None))
+    }
+
+    /// Returns the locations with their numbers, in the configured order.
+    ///
+    /// Locations recorded by another process come first, see [RemoteError](crate::RemoteError).
+    pub(crate) fn frames<'a>(&self, error: &'a Error) -> impl Iterator<Item = (usize, Frame<'a>)> {
+        let remote = error.remote_error().map(|v| v.frames()).unwrap_or_default();
+        let local = error.location().unwrap_or_default();
+        let mut frames: Vec<_> = remote
+            .iter()
+            .map(Frame::Remote)
+            .chain(local.iter().enumerate().map(|(i, v)| Frame::Local(i, v)))
+            .enumerate()
+            .collect();
+        if self.order == Order::OutermostFirst {
+            frames.reverse();
+        }
+        frames.into_iter()
+    }
+
+    fn fmt(&self, f: &mut Formatter<'_>, error: &Error) -> std::fmt::Result {
+        self.fmt_message(f, error)?;
+        for (i, frame) in self.frames(error) {
+            f.write_str(self.separator())?;
+            self.fmt_frame(f, error, i, frame)?;
+        }
+
+        if f.alternate() {
+            for (i, v) in error.sources().skip(1).enumerate() {
+                if i == 0 {
+                    let sep = match self.layout {
+                        Layout::MultiLine => "\n\n",
+                        Layout::Compact => " | ",
+                    };
+                    f.write_str(sep)?;
+                    self.paint(f, BOLD, "Caused by:")?;
+                }
+                match self.layout {
+                    Layout::MultiLine => write!(f, "\n    {}: {}", i, v)?,
+                    Layout::Compact => write!(f, " {}: {}", i, v)?,
+                }
+            }
+        }
+
+        // Only the alternate format, so that `to_string` does not depend on `RUST_BACKTRACE`.
+        #[cfg(feature = "backtrace")]
+        if f.alternate() && !self.is_compact() {
+            self.fmt_backtrace(f, error)?;
+        }
+        #[cfg(feature = "spantrace")]
+        if f.alternate() && !self.is_compact() {
+            self.fmt_span_trace(f, error)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the backtrace of the error if one was captured.
+    #[cfg(feature = "backtrace")]
+    pub(crate) fn fmt_backtrace(&self, f: &mut Formatter<'_>, error: &Error) -> std::fmt::Result {
+        self.fmt_captured(f, &error.0.backtrace, false)
+    }
+
+    /// Writes the span trace of the error if one was captured.
+    #[cfg(feature = "spantrace")]
+    pub(crate) fn fmt_span_trace(&self, f: &mut Formatter<'_>, error: &Error) -> std::fmt::Result {
+        let span_trace = error.span_trace();
+        if span_trace.status() == tracing_error::SpanTraceStatus::CAPTURED {
+            f.write_str("\n\n")?;
+            self.paint(f, BOLD, "Span trace:")?;
+            write!(f, "\n{}", span_trace)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a backtrace if it was captured, with the frames of the standard library and the
+    /// runtime if `full`.
+    pub(crate) fn fmt_captured(
+        &self,
+        f: &mut Formatter<'_>,
+        backtrace: &std::backtrace::Backtrace,
+        full: bool,
+    ) -> std::fmt::Result {
+        if backtrace.status() == std::backtrace::BacktraceStatus::Captured {
+            f.write_str("\n\n")?;
+            self.paint(f, BOLD, "Stack backtrace:")?;
+            match full {
+                true => write!(f, "\n{:#}", backtrace)?,
+                false => write!(f, "\n{}", backtrace)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for TraceFormat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) const BOLD: &str = "1";
+pub(crate) const DIM: &str = "2";
+pub(crate) const GREEN: &str = "32";
+pub(crate) const YELLOW: &str = "33";
+pub(crate) const CYAN: &str = "36";
+
+/// Returns the path of a file from the `src` directory of its crate, if there is one.
+fn crate_relative(file: &str) -> &str {
+    let i = match (file.rfind("/src/"), file.rfind("\\src\\")) {
+        (Some(a), Some(b)) => a.max(b),
+        (Some(v), None) | (None, Some(v)) => v,
+        (None, None) => return file,
+    };
+    &file[i + 1..]
+}
+
+/// A location of an [Error], see [TraceFormat::frames].
+#[derive(Clone, Copy)]
+pub(crate) enum Frame<'a> {
+    /// A location recorded by this process, with its index in [Error::location].
+    Local(usize, &'a Location),
+    Remote(&'a RemoteFrame),
+}
+
+/// An [Error] written with a [TraceFormat], see [Error::format].
+pub struct Formatted<'a> {
+    pub(crate) error: &'a Error,
+    pub(crate) format: TraceFormat,
+}
+
+impl Display for Formatted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.format.fmt(f, self.error)
+    }
+}
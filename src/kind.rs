@@ -0,0 +1,81 @@
+use std::fmt::{Display, Formatter};
+
+/// Category of an [Error](crate::Error), for mapping it to e.g. an HTTP or gRPC status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A requested entity was not found.
+    NotFound,
+    /// The input was invalid.
+    InvalidInput,
+    /// The caller is not allowed to perform the operation.
+    PermissionDenied,
+    /// An entity to be created already exists.
+    AlreadyExists,
+    /// A service or resource is temporarily unavailable.
+    Unavailable,
+    /// An internal error.
+    Internal,
+    /// A category not covered by the other variants.
+    Custom(&'static str),
+}
+
+impl ErrorKind {
+    /// Returns the name of the kind, e.g. `not_found`, or the name of a [ErrorKind::Custom] kind.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::InvalidInput => "invalid_input",
+            Self::PermissionDenied => "permission_denied",
+            Self::AlreadyExists => "already_exists",
+            Self::Unavailable => "unavailable",
+            Self::Internal => "internal",
+            Self::Custom(v) => v,
+        }
+    }
+
+    /// Returns the kind named `name` other than [ErrorKind::Custom], see [ErrorKind::as_str].
+    #[cfg(feature = "serde")]
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "not_found" => Some(Self::NotFound),
+            "invalid_input" => Some(Self::InvalidInput),
+            "permission_denied" => Some(Self::PermissionDenied),
+            "already_exists" => Some(Self::AlreadyExists),
+            "unavailable" => Some(Self::Unavailable),
+            "internal" => Some(Self::Internal),
+            _ => None,
+        }
+    }
+
+    /// Returns the process exit code of the kind, following `sysexits.h`.
+    ///
+    /// [ErrorKind::Custom] kinds exit with 1.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::NotFound => 66,         // EX_NOINPUT
+            Self::InvalidInput => 65,     // EX_DATAERR
+            Self::PermissionDenied => 77, // EX_NOPERM
+            Self::AlreadyExists => 73,    // EX_CANTCREAT
+            Self::Unavailable => 69,      // EX_UNAVAILABLE
+            Self::Internal => 70,         // EX_SOFTWARE
+            Self::Custom(_) => 1,
+        }
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ErrorKind {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
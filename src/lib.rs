@@ -1,11 +1,49 @@
 #![doc = include_str!("../README.md")]
+#![cfg_attr(
+    all(feature = "backtrace", conerror_nightly),
+    feature(error_generic_member_access)
+)]
 
 use std::any::TypeId;
+#[cfg(feature = "backtrace")]
+use std::backtrace::Backtrace;
+use std::borrow::Cow;
 use std::fmt::{Debug, Display, Formatter};
 use std::mem::ManuallyDrop;
 use std::ptr;
 
-pub use conerror_macro::conerror;
+pub use conerror_macro::{conerror, Error};
+#[cfg(feature = "spantrace")]
+use tracing_error::SpanTrace;
+
+pub use attachment::Attachment;
+pub use ext::{OptionExt, ResultExt};
+use format::{Formatted, TraceFormat};
+pub use kind::ErrorKind;
+pub use panic::{install_panic_hook, install_panic_hook_with, PanicMessage};
+pub use remote::{RemoteError, RemoteFrame};
+pub use report::{MainResult, Report};
+#[cfg(feature = "serde")]
+pub use schema::{Schema, Serialized};
+
+#[doc(hidden)]
+pub mod __private;
+mod attachment;
+mod ext;
+pub mod format;
+mod kind;
+#[cfg(feature = "log")]
+mod logging;
+mod macros;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod panic;
+mod remote;
+pub mod report;
+#[cfg(feature = "serde")]
+mod schema;
+#[cfg(feature = "tracing")]
+mod trace;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -13,20 +51,46 @@ use inner::*;
 
 #[cfg(feature = "send_sync")]
 mod inner {
+    use std::any::Any;
+    use std::fmt::{Debug, Display};
+
     pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
 
     pub trait ErrorTrait: std::error::Error + Send + Sync {}
 
     impl<T: std::error::Error + Send + Sync> ErrorTrait for T {}
+
+    pub trait AttachmentValue: Any + Debug + Display + Send + Sync {
+        fn as_any(&self) -> &dyn Any;
+    }
+
+    impl<T: Any + Debug + Display + Send + Sync> AttachmentValue for T {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
 }
 
 #[cfg(not(feature = "send_sync"))]
 mod inner {
+    use std::any::Any;
+    use std::fmt::{Debug, Display};
+
     pub type BoxError = Box<dyn std::error::Error>;
 
     pub trait ErrorTrait: std::error::Error {}
 
     impl<T: std::error::Error> ErrorTrait for T {}
+
+    pub trait AttachmentValue: Any + Debug + Display {
+        fn as_any(&self) -> &dyn Any;
+    }
+
+    impl<T: Any + Debug + Display> AttachmentValue for T {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
 }
 
 /// Represents an error with additional location information.
@@ -35,17 +99,23 @@ pub struct Error(Box<Inner>);
 impl Error {
     /// Creates a new [Error] with location information.
     ///
+    /// With the `backtrace` feature, a [Backtrace](std::backtrace::Backtrace) is captured as well
+    /// if `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` enables it. With the `spantrace` feature,
+    /// the `tracing` spans the error occurred in are captured.
+    ///
     /// # Parameters
     ///
     /// - `error`: The error to wrap.
     /// - `file`: The file where the error occurred.
     /// - `line`: The line number where the error occurred.
+    /// - `column`: The column number where the error occurred.
     /// - `func`: The function where the error occurred.
     /// - `module`: The module where the error occurred.
     pub fn new<T>(
         error: T,
         file: &'static str,
         line: u32,
+        column: u32,
         func: &'static str,
         module: &'static str,
     ) -> Self
@@ -57,14 +127,26 @@ impl Error {
             location: Some(vec![Location {
                 file,
                 line,
+                column,
                 func,
                 module,
             }]),
             context: Vec::new(),
+            code: None,
+            kind: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture(),
+            #[cfg(feature = "spantrace")]
+            span_trace: SpanTrace::capture(),
+            #[cfg(feature = "opentelemetry")]
+            type_name: std::any::type_name::<T>(),
         }))
     }
 
     /// Creates a new [Error] without location information.
+    ///
+    /// Like [Error::new], a [Backtrace](std::backtrace::Backtrace) is captured with the
+    /// `backtrace` feature.
     pub fn plain<T>(error: T) -> Self
     where
         T: Into<BoxError>,
@@ -73,6 +155,14 @@ impl Error {
             source: error.into(),
             location: None,
             context: Vec::new(),
+            code: None,
+            kind: None,
+            #[cfg(feature = "backtrace")]
+            backtrace: Backtrace::capture(),
+            #[cfg(feature = "spantrace")]
+            span_trace: SpanTrace::capture(),
+            #[cfg(feature = "opentelemetry")]
+            type_name: std::any::type_name::<T>(),
         }))
     }
 
@@ -80,107 +170,368 @@ impl Error {
     ///
     /// If the provided error is not of type [Error], it creates a new [Error] with location information.
     /// If the provided error is of type [Error], it adds the location information if it was not created by [Error::plain].
+    /// If its last location has no function and is on the same line, e.g. recorded by
+    /// [ResultExt::context], the function and module are filled in instead.
     ///
     /// # Parameters
     ///
     /// - `error`: The error to wrap.
     /// - `file`: The file where the error occurred.
     /// - `line`: The line number where the error occurred.
+    /// - `column`: The column number where the error occurred.
     /// - `func`: The function where the error occurred.
     /// - `module`: The module where the error occurred.
     pub fn chain<T>(
         error: T,
         file: &'static str,
         line: u32,
+        column: u32,
         func: &'static str,
         module: &'static str,
     ) -> Self
     where
         T: ErrorTrait + 'static,
     {
-        if TypeId::of::<T>() == TypeId::of::<Self>() {
-            let error = ManuallyDrop::new(error);
-            // SAFETY: type checked
-            let mut error = unsafe { ptr::read(&error as *const _ as *const Self) };
-            if let Some(ref mut location) = error.0.location {
-                location.push(Location {
-                    file,
-                    line,
-                    func,
-                    module,
-                });
+        match Self::cast(error) {
+            Ok(mut error) => {
+                let Some(ref mut location) = error.0.location else {
+                    return error;
+                };
+                // A `?` right after e.g. `ResultExt::context` names the location it recorded,
+                // instead of adding a second one for the same call.
+                match location.last_mut() {
+                    Some(last)
+                        if last.func.is_empty() && last.file == file && last.line == line =>
+                    {
+                        last.func = func;
+                        last.module = module;
+                    }
+                    _ => location.push(Location {
+                        file,
+                        line,
+                        column,
+                        func,
+                        module,
+                    }),
+                }
+                error
             }
-            return error;
+            Err(error) => Self::new(error, file, line, column, func, module),
+        }
+    }
+
+    /// Converts an error into an [Error] located at the caller, unless it is already an [Error].
+    ///
+    /// The function and module of the location are unknown and left empty.
+    #[track_caller]
+    pub(crate) fn caller<T>(error: T) -> Self
+    where
+        T: Into<BoxError> + 'static,
+    {
+        match Self::cast(error) {
+            Ok(error) => error,
+            Err(error) => {
+                let caller = std::panic::Location::caller();
+                Self::new(error, caller.file(), caller.line(), caller.column(), "", "")
+            }
+        }
+    }
+
+    /// Marks an [Error] as created from a message, which reports `conerror::Error` as the type
+    /// of its exception instead of the type of the message.
+    #[cfg_attr(not(feature = "opentelemetry"), allow(unused_mut))]
+    pub(crate) fn mark_message(mut self) -> Self {
+        #[cfg(feature = "opentelemetry")]
+        {
+            self.0.type_name = "conerror::Error";
+        }
+        self
+    }
+
+    /// Returns the error itself if it is of type [Error].
+    fn cast<T>(error: T) -> std::result::Result<Self, T>
+    where
+        T: 'static,
+    {
+        if TypeId::of::<T>() != TypeId::of::<Self>() {
+            return Err(error);
         }
 
-        Self::new(error, file, line, func, module)
+        let error = ManuallyDrop::new(error);
+        // SAFETY: type checked
+        Ok(unsafe { ptr::read(&error as *const _ as *const Self) })
     }
 
+    /// Adds context to the location that was recorded last, or to the error itself if there is none.
     pub fn context(mut self, context: impl ToString) -> Self {
-        self.0.context.push(context.to_string());
+        self.push_context(ContextValue::Message(context.to_string()));
         self
     }
 
+    /// Attaches a typed value under the given key to the location that was recorded last,
+    /// or to the error itself if there is none.
+    ///
+    /// ```
+    /// let e = conerror::Error::plain("not found").attach("user_id", 42u64);
+    /// assert_eq!(e.attachment("user_id").and_then(|v| v.value::<u64>()), Some(&42));
+    /// assert_eq!(e.attached::<u64>(), Some(&42));
+    /// assert_eq!(e.to_string(), "not found (user_id=42)");
+    /// ```
+    pub fn attach<T>(mut self, key: impl Into<Cow<'static, str>>, value: T) -> Self
+    where
+        T: AttachmentValue,
+    {
+        self.push_context(ContextValue::Attachment(Attachment {
+            key: key.into(),
+            value: Box::new(value),
+        }));
+        self
+    }
+
+    /// Sets the error code, replacing any code set before.
+    ///
+    /// The code and [ErrorKind] are kept when the error is passed on with `?`.
+    ///
+    /// ```
+    /// use conerror::ErrorKind;
+    ///
+    /// let e = conerror::Error::plain("user not found")
+    ///     .with_code("USER_NOT_FOUND")
+    ///     .with_kind(ErrorKind::NotFound);
+    /// assert_eq!(e.code(), Some("USER_NOT_FOUND"));
+    /// assert_eq!(e.kind(), Some(ErrorKind::NotFound));
+    /// ```
+    pub fn with_code(mut self, code: impl Into<Cow<'static, str>>) -> Self {
+        self.0.code = Some(code.into());
+        self
+    }
+
+    /// Sets the [ErrorKind], replacing any kind set before.
+    pub fn with_kind(mut self, kind: ErrorKind) -> Self {
+        self.0.kind = Some(kind);
+        self
+    }
+
+    /// Returns the error code.
+    pub fn code(&self) -> Option<&str> {
+        self.0.code.as_deref()
+    }
+
+    /// Returns the [ErrorKind].
+    pub fn kind(&self) -> Option<ErrorKind> {
+        self.0.kind
+    }
+
+    /// Returns an iterator over the attachments, in the order they were attached.
+    pub fn attachments(&self) -> impl Iterator<Item = &Attachment> {
+        self.0
+            .context
+            .iter()
+            .filter_map(|v| v.value.as_attachment())
+    }
+
+    /// Returns the last attachment with the given key.
+    pub fn attachment(&self, key: &str) -> Option<&Attachment> {
+        self.attachments().filter(|v| v.key == key).last()
+    }
+
+    /// Returns the value of the last attachment of type `T`.
+    pub fn attached<T>(&self) -> Option<&T>
+    where
+        T: 'static,
+    {
+        self.attachments().filter_map(|v| v.value::<T>()).last()
+    }
+
+    /// Returns the backtrace captured when the error was created.
+    ///
+    /// Its [status](Backtrace::status) tells whether it was captured, see [Error::new].
+    #[cfg(feature = "backtrace")]
+    pub fn backtrace(&self) -> &Backtrace {
+        &self.0.backtrace
+    }
+
+    /// Returns the `tracing` spans that were entered when the error was created.
+    ///
+    /// They are only captured if the subscriber has a `tracing_error::ErrorLayer`.
+    #[cfg(feature = "spantrace")]
+    pub fn span_trace(&self) -> &SpanTrace {
+        &self.0.span_trace
+    }
+
     /// Returns the location information.
     pub fn location(&self) -> Option<&[Location]> {
-        self.0.location.as_ref().map(|v| v.as_slice())
+        self.0.location.as_deref()
+    }
+
+    /// Returns a value that writes the error with the given [TraceFormat] when displayed.
+    pub fn format(&self, format: TraceFormat) -> Formatted<'_> {
+        Formatted {
+            error: self,
+            format,
+        }
     }
 
     /// Returns the error message
     pub fn message(&self) -> String {
         let mut s = String::with_capacity(self.0.context.len() * 16);
         for c in self.0.context.iter().rev() {
-            s.push_str(c);
-            s.push_str(": ");
+            if let Some(c) = c.value.as_message() {
+                s.push_str(c);
+                s.push_str(": ");
+            }
+        }
+        if let Some(remote) = self.remote_error() {
+            for c in remote.frames.iter().rev().flat_map(|v| &v.context) {
+                s.push_str(c);
+                s.push_str(": ");
+            }
         }
         s.push_str(&self.0.source.to_string());
         s
     }
-}
 
-#[cfg(feature = "serde")]
-impl serde::Serialize for Error {
-    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        use serde::ser::SerializeStruct;
-
-        let mut s = serializer.serialize_struct("Error", 2)?;
-        s.serialize_field("message", &self.message())?;
-        let location = self
+    fn push_context(&mut self, value: ContextValue) {
+        let frame = self
             .0
             .location
             .as_ref()
-            .map(|v| v.iter().map(Location::to_string).collect())
-            .unwrap_or(Vec::new());
-        s.serialize_field("location", &location)?;
-        s.end()
+            .and_then(|v| v.len().checked_sub(1));
+        self.0.context.push(Context { frame, value });
+    }
+
+    /// Returns the context messages of a location, or of the error itself if `frame` is `None`,
+    /// the last added first.
+    fn frame_messages(&self, frame: Option<usize>) -> impl Iterator<Item = &str> {
+        self.0
+            .context
+            .iter()
+            .rev()
+            .filter(move |v| v.frame == frame)
+            .filter_map(|v| v.value.as_message())
+    }
+
+    /// Returns the attachments of a location, or of the error itself if `frame` is `None`.
+    fn frame_attachments(&self, frame: Option<usize>) -> impl Iterator<Item = &Attachment> {
+        self.0
+            .context
+            .iter()
+            .filter(move |v| v.frame == frame)
+            .filter_map(|v| v.value.as_attachment())
+    }
+
+    /// Returns an iterator over the wrapped error and every error in its source chain.
+    pub fn sources(&self) -> Sources<'_> {
+        Sources {
+            next: Some(&*self.0.source),
+        }
+    }
+
+    /// Returns the last error in the source chain of the wrapped error.
+    pub fn root_cause(&self) -> &(dyn std::error::Error + 'static) {
+        // `sources` always yields the wrapped error
+        self.sources().last().unwrap_or(&*self.0.source)
+    }
+
+    /// Returns `true` if the wrapped error or any error in its source chain is of type `E`.
+    pub fn is<E>(&self) -> bool
+    where
+        E: std::error::Error + 'static,
+    {
+        self.downcast_ref::<E>().is_some()
+    }
+
+    /// Returns a reference to the first error of type `E` found in the wrapped error and its source chain.
+    ///
+    /// ```
+    /// use conerror::conerror;
+    ///
+    /// #[conerror]
+    /// fn read() -> conerror::Result<Vec<u8>> {
+    ///     Ok(std::fs::read("non_exists_config.toml")?)
+    /// }
+    ///
+    /// let e = read().unwrap_err();
+    /// let kind = e.downcast_ref::<std::io::Error>().map(|v| v.kind());
+    /// assert_eq!(kind, Some(std::io::ErrorKind::NotFound));
+    /// assert_eq!(e.location().unwrap().len(), 1);
+    /// ```
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: std::error::Error + 'static,
+    {
+        self.sources().find_map(|v| v.downcast_ref::<E>())
+    }
+
+    /// Returns a mutable reference to the wrapped error if it is of type `E`.
+    ///
+    /// Unlike [Error::downcast_ref], the source chain is not searched,
+    /// since [std::error::Error::source] only gives shared references.
+    pub fn downcast_mut<E>(&mut self) -> Option<&mut E>
+    where
+        E: std::error::Error + 'static,
+    {
+        self.0.source.downcast_mut::<E>()
+    }
+
+    /// Takes the wrapped error if it is of type `E`, otherwise returns the [Error] unchanged.
+    ///
+    /// Like [Error::downcast_mut], the source chain is not searched.
+    pub fn downcast<E>(self) -> std::result::Result<E, Self>
+    where
+        E: std::error::Error + 'static,
+    {
+        let Inner {
+            source,
+            location,
+            context,
+            code,
+            kind,
+            #[cfg(feature = "backtrace")]
+            backtrace,
+            #[cfg(feature = "spantrace")]
+            span_trace,
+            #[cfg(feature = "opentelemetry")]
+            type_name,
+        } = *self.0;
+        match source.downcast::<E>() {
+            Ok(v) => Ok(*v),
+            Err(source) => Err(Self(Box::new(Inner {
+                source,
+                location,
+                context,
+                code,
+                kind,
+                #[cfg(feature = "backtrace")]
+                backtrace,
+                #[cfg(feature = "spantrace")]
+                span_trace,
+                #[cfg(feature = "opentelemetry")]
+                type_name,
+            }))),
+        }
     }
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Error")
-            .field("source", &self.0.source)
+        let mut f = f.debug_struct("Error");
+        f.field("source", &self.0.source)
             .field("location", &self.0.location)
             .field("context", &self.0.context)
-            .finish()
+            .field("code", &self.0.code)
+            .field("kind", &self.0.kind);
+        #[cfg(feature = "backtrace")]
+        f.field("backtrace", &self.0.backtrace);
+        #[cfg(feature = "spantrace")]
+        f.field("span_trace", &self.0.span_trace);
+        f.finish()
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for c in self.0.context.iter().rev() {
-            write!(f, "{}: ", c)?;
-        }
-        Display::fmt(&self.0.source, f)?;
-        if let Some(ref location) = self.0.location {
-            for (i, v) in location.iter().enumerate() {
-                write!(f, "\n#{} {}", i, v)?;
-            }
-        }
-        Ok(())
+        Display::fmt(&self.format(TraceFormat::new()), f)
     }
 }
 
@@ -188,12 +539,74 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         Some(&*self.0.source)
     }
+
+    #[cfg(all(feature = "backtrace", conerror_nightly))]
+    fn provide<'a>(&'a self, request: &mut std::error::Request<'a>) {
+        // A backtrace of the wrapped error reaches further down than the one captured here.
+        self.0.source.provide(request);
+        request.provide_ref::<Backtrace>(&self.0.backtrace);
+    }
+}
+
+/// Iterator over an error and its source chain, see [Error::sources].
+#[derive(Clone)]
+pub struct Sources<'a> {
+    next: Option<&'a (dyn std::error::Error + 'static)>,
+}
+
+impl<'a> Iterator for Sources<'a> {
+    type Item = &'a (dyn std::error::Error + 'static);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.next?;
+        self.next = next.source();
+        Some(next)
+    }
 }
 
 struct Inner {
     source: BoxError,
     location: Option<Vec<Location>>,
-    context: Vec<String>,
+    context: Vec<Context>,
+    code: Option<Cow<'static, str>>,
+    kind: Option<ErrorKind>,
+    #[cfg(feature = "backtrace")]
+    backtrace: Backtrace,
+    #[cfg(feature = "spantrace")]
+    span_trace: SpanTrace,
+    /// Type of the wrapped error, given by the caller of [Error::new] or [Error::plain],
+    /// or set by [Error::mark_message].
+    #[cfg(feature = "opentelemetry")]
+    type_name: &'static str,
+}
+
+#[derive(Debug)]
+struct Context {
+    /// Index of the location that was recorded last when the context was added.
+    frame: Option<usize>,
+    value: ContextValue,
+}
+
+#[derive(Debug)]
+enum ContextValue {
+    Message(String),
+    Attachment(Attachment),
+}
+
+impl ContextValue {
+    fn as_message(&self) -> Option<&str> {
+        match self {
+            Self::Message(v) => Some(v),
+            Self::Attachment(_) => None,
+        }
+    }
+
+    fn as_attachment(&self) -> Option<&Attachment> {
+        match self {
+            Self::Message(_) => None,
+            Self::Attachment(v) => Some(v),
+        }
+    }
 }
 
 /// Represents the location where an error occurred.
@@ -201,17 +614,23 @@ struct Inner {
 pub struct Location {
     pub file: &'static str,
     pub line: u32,
+    pub column: u32,
+    /// Empty if the location was recorded by [ResultExt] or [OptionExt] outside of `#[conerror]`.
     pub func: &'static str,
-    /// Module path for function, struct/trait name for method.
+    /// Module path for function, followed by the type or trait as written in the `impl` or `trait`
+    /// for method, e.g. `app::Cache<K>` or `app::<App as Service>`.
     pub module: &'static str,
 }
 
 impl Display for Location {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.func.is_empty() {
+            return write!(f, "{}:{}:{}", self.file, self.line, self.column);
+        }
         write!(
             f,
-            "{}:{} {}::{}()",
-            self.file, self.line, self.module, self.func
+            "{}:{}:{} {}::{}()",
+            self.file, self.line, self.column, self.module, self.func
         )
     }
 }
@@ -0,0 +1,138 @@
+use log::kv::{Error as KvError, Key, Source, Value, VisitSource};
+use log::{Level, Record};
+
+use crate::format::{Frame, TraceFormat};
+use crate::Error;
+
+impl Error {
+    /// Logs the error at `level` through the `log` crate, located where this method is called.
+    ///
+    /// The target of the record is the module of the last location recorded in this process,
+    /// where the error was passed on last, so that the record is filtered like the other records
+    /// of the module, e.g. by `RUST_LOG=app=warn`. It is `conerror` if there is none, e.g. for an
+    /// error located by [ResultExt](crate::ResultExt) outside of `#[conerror]`.
+    ///
+    /// The message of the record is [Error::message]. The code, kind and context of the error,
+    /// and each location with its context, are key-value pairs, e.g.
+    /// `location.0="src/db.rs:40:9 app::db::query()"` and `location.0.context="Failed to query"`.
+    ///
+    /// ```
+    /// use std::sync::Mutex;
+    ///
+    /// static RECORDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    ///
+    /// struct Logger;
+    ///
+    /// impl log::Log for Logger {
+    ///     fn enabled(&self, _: &log::Metadata) -> bool {
+    ///         true
+    ///     }
+    ///
+    ///     fn log(&self, record: &log::Record) {
+    ///         let mut s = format!("{} {} {}", record.level(), record.target(), record.args());
+    ///         for key in ["code", "location.0", "location.0.context"] {
+    ///             let value = record.key_values().get(key.into()).unwrap();
+    ///             s.push_str(&format!(" {}={:?}", key, value.to_string()));
+    ///         }
+    ///         RECORDS.lock().unwrap().push(s);
+    ///     }
+    ///
+    ///     fn flush(&self) {}
+    /// }
+    ///
+    /// log::set_logger(&Logger).unwrap();
+    /// log::set_max_level(log::LevelFilter::Trace);
+    ///
+    /// let e = conerror::Error::new("timeout", "src/db.rs", 40, 9, "query", "app::db")
+    ///     .context("Failed to query")
+    ///     .with_code("DB");
+    /// e.log(log::Level::Warn);
+    /// assert_eq!(
+    ///     RECORDS.lock().unwrap()[0],
+    ///     r#"WARN app::db Failed to query: timeout code="DB" location.0="src/db.rs:40:9 app::db::query()" location.0.context="Failed to query""#,
+    /// );
+    ///
+    /// let e = conerror::Error::chain(e, "src/main.rs", 8, 13, "main", "app");
+    /// e.log(log::Level::Error);
+    /// assert!(RECORDS.lock().unwrap()[1].starts_with("ERROR app Failed to query: timeout"));
+    /// ```
+    #[track_caller]
+    pub fn log(&self, level: Level) {
+        let target = self.log_target();
+        if !log::log_enabled!(target: target, level) {
+            return;
+        }
+
+        let caller = std::panic::Location::caller();
+        log::logger().log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .file(Some(caller.file()))
+                .line(Some(caller.line()))
+                .args(format_args!("{}", self.message()))
+                .key_values(&Fields::new(self))
+                .build(),
+        );
+    }
+}
+
+impl Error {
+    /// Returns the module of the last location with one, see [Error::log].
+    fn log_target(&self) -> &'static str {
+        let location = self.location().unwrap_or_default();
+        let mut modules = location.iter().rev().map(|v| v.module);
+        modules.find(|v| !v.is_empty()).unwrap_or("conerror")
+    }
+}
+
+/// Key-value pairs of an error, see [Error::log].
+struct Fields {
+    fields: Vec<(String, String)>,
+}
+
+impl Fields {
+    fn new(error: &Error) -> Self {
+        let mut fields = Vec::new();
+        if let Some(code) = error.code() {
+            fields.push(("code".to_string(), code.to_string()));
+        }
+        if let Some(kind) = error.kind() {
+            fields.push(("kind".to_string(), kind.to_string()));
+        }
+        let context: Vec<_> = error.frame_messages(None).collect();
+        if !context.is_empty() {
+            fields.push(("context".to_string(), context.join(": ")));
+        }
+
+        for (i, frame) in TraceFormat::new().frames(error) {
+            let (location, context) = match frame {
+                Frame::Local(j, v) => (v.to_string(), error.frame_messages(Some(j)).collect()),
+                Frame::Remote(v) => {
+                    let location = match v.service {
+                        Some(ref service) => format!("[remote {}] {}", service, v),
+                        None => format!("[remote] {}", v),
+                    };
+                    (
+                        location,
+                        v.context.iter().map(String::as_str).collect::<Vec<_>>(),
+                    )
+                }
+            };
+            fields.push((format!("location.{}", i), location));
+            if !context.is_empty() {
+                fields.push((format!("location.{}.context", i), context.join(": ")));
+            }
+        }
+        Self { fields }
+    }
+}
+
+impl Source for Fields {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), KvError> {
+        for (key, value) in &self.fields {
+            visitor.visit_pair(Key::from_str(key), Value::from(value.as_str()))?;
+        }
+        Ok(())
+    }
+}
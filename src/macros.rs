@@ -0,0 +1,158 @@
+/// Creates an [Error](crate::Error) with the current location.
+///
+/// It accepts a format string with its arguments, or any value that can be converted into a
+/// boxed error. Inside a `#[conerror]` function, the location is recorded under the name of the
+/// enclosing function, closure or async block.
+///
+/// ```
+/// use conerror::{conerr, conerror};
+///
+/// #[conerror]
+/// fn parse(input: &str) -> conerror::Result<u8> {
+///     if input.is_empty() {
+///         return Err(conerr!("empty input"));
+///     }
+///     input.parse().map_err(|err| conerr!("invalid input {input:?}: {err}"))
+/// }
+///
+/// let e = parse("").unwrap_err();
+/// assert_eq!(e.message(), "empty input");
+/// assert_eq!(e.location().unwrap()[0].func, "parse");
+/// ```
+#[macro_export]
+macro_rules! conerr {
+    (@frame($func:expr, $module:expr) $msg:literal $(,)?) => {
+        $crate::__private::message($crate::Error::new(
+            ::std::format!($msg),
+            ::std::file!(),
+            ::std::line!(),
+            ::std::column!(),
+            $func,
+            $module,
+        ))
+    };
+    (@frame($func:expr, $module:expr) $err:expr $(,)?) => {{
+        let error = $err;
+        let code = $crate::__code!(&error);
+        code.apply($crate::Error::new(
+            error,
+            ::std::file!(),
+            ::std::line!(),
+            ::std::column!(),
+            $func,
+            $module,
+        ))
+    }};
+    (@frame($func:expr, $module:expr) $fmt:expr, $($arg:tt)*) => {
+        $crate::__private::message($crate::Error::new(
+            ::std::format!($fmt, $($arg)*),
+            ::std::file!(),
+            ::std::line!(),
+            ::std::column!(),
+            $func,
+            $module,
+        ))
+    };
+    ($($arg:tt)+) => {{
+        let (func, module) = $crate::__frame!();
+        $crate::conerr!(@frame(func, module) $($arg)+)
+    }};
+}
+
+/// Returns early with an [Error](crate::Error) created by [conerr!].
+///
+/// ```
+/// use conerror::conerror;
+///
+/// #[conerror]
+/// fn check(len: usize) -> conerror::Result<()> {
+///     if len > 8 {
+///         conerror::bail!("length {} exceeds {}", len, 8);
+///     }
+///     Ok(())
+/// }
+///
+/// assert_eq!(check(9).unwrap_err().message(), "length 9 exceeds 8");
+/// ```
+#[macro_export]
+macro_rules! bail {
+    (@frame($func:expr, $module:expr) $($arg:tt)+) => {
+        return ::std::result::Result::Err(::std::convert::From::from(
+            $crate::conerr!(@frame($func, $module) $($arg)+),
+        ))
+    };
+    ($($arg:tt)+) => {{
+        let (func, module) = $crate::__frame!();
+        $crate::bail!(@frame(func, module) $($arg)+)
+    }};
+}
+
+/// Returns early with an [Error](crate::Error) created by [conerr!] if the condition is false.
+///
+/// Without a message, the error describes the failed condition.
+///
+/// ```
+/// use conerror::conerror;
+///
+/// #[conerror]
+/// fn check(len: usize) -> conerror::Result<()> {
+///     conerror::ensure!(len <= 8);
+///     conerror::ensure!(len > 0, "length must not be zero");
+///     Ok(())
+/// }
+///
+/// assert_eq!(check(9).unwrap_err().message(), "condition failed: `len <= 8`");
+/// assert_eq!(check(0).unwrap_err().message(), "length must not be zero");
+/// ```
+#[macro_export]
+macro_rules! ensure {
+    (@frame($func:expr, $module:expr) $cond:expr $(,)?) => {
+        if !$cond {
+            $crate::bail!(
+                @frame($func, $module)
+                "{}",
+                ::std::concat!("condition failed: `", ::std::stringify!($cond), "`")
+            );
+        }
+    };
+    (@frame($func:expr, $module:expr) $cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!(@frame($func, $module) $($arg)+);
+        }
+    };
+    ($cond:expr $(,)?) => {
+        if !$cond {
+            $crate::bail!(
+                "{}",
+                ::std::concat!("condition failed: `", ::std::stringify!($cond), "`")
+            );
+        }
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            $crate::bail!($($arg)+);
+        }
+    };
+}
+
+/// Returns the name and module of the enclosing function, for use outside of `#[conerror]`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __frame {
+    () => {{
+        fn f() {}
+        $crate::__private::frame(::std::any::type_name_of_val(&f))
+    }};
+}
+
+/// Returns the [Code](crate::__private::Code) of a reference to an error,
+/// which is only set for errors deriving `conerror::Error`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __code {
+    ($err:expr) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{ProbeCoded as _, ProbePlain as _};
+        (&&$crate::__private::Probe($err)).code()
+    }};
+}
@@ -0,0 +1,103 @@
+use std::fmt::{Display, Formatter};
+
+use opentelemetry::trace::get_active_span;
+use opentelemetry::KeyValue;
+
+use crate::format::TraceFormat;
+use crate::Error;
+
+impl Error {
+    /// Records the error as an `exception` event on the current OpenTelemetry span.
+    ///
+    /// See [Error::exception_attributes] for the attributes of the event.
+    ///
+    /// ```
+    /// use opentelemetry::trace::{Tracer, TracerProvider};
+    /// use opentelemetry::Value;
+    /// use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+    ///
+    /// let exporter = InMemorySpanExporter::default();
+    /// let provider = SdkTracerProvider::builder()
+    ///     .with_simple_exporter(exporter.clone())
+    ///     .build();
+    ///
+    /// let e = conerror::Error::new(
+    ///     std::io::Error::other("timeout"),
+    ///     "src/db.rs",
+    ///     40,
+    ///     9,
+    ///     "query",
+    ///     "app::db",
+    /// );
+    /// provider.tracer("app").in_span("query", |_| e.record_exception());
+    ///
+    /// let spans = exporter.get_finished_spans().unwrap();
+    /// let event = &spans[0].events.events[0];
+    /// assert_eq!(event.name, "exception");
+    /// let attribute = |key: &str| {
+    ///     let v = event.attributes.iter().find(|v| v.key.as_str() == key);
+    ///     v.map(|v| v.value.clone())
+    /// };
+    /// assert_eq!(attribute("exception.type"), Some(Value::from("std::io::error::Error")));
+    /// assert_eq!(attribute("exception.message"), Some(Value::from("timeout")));
+    /// assert_eq!(
+    ///     attribute("exception.stacktrace"),
+    ///     Some(Value::from("#0 src/db.rs:40:9 app::db::query()")),
+    /// );
+    ///
+    /// let e = conerror::conerr!("missing user {}", 7);
+    /// let attributes = e.exception_attributes();
+    /// assert_eq!(attributes[0].value, Value::from("conerror::Error"));
+    ///
+    /// use conerror::OptionExt;
+    /// let e = None::<u8>.context("missing user").unwrap_err();
+    /// assert_eq!(e.exception_attributes()[0].value, Value::from("conerror::Error"));
+    /// ```
+    pub fn record_exception(&self) {
+        get_active_span(|span| span.add_event("exception", self.exception_attributes()));
+    }
+
+    /// Returns the attributes of an `exception` event following the OpenTelemetry semantic
+    /// conventions, for recording the error on a span of your own:
+    ///
+    /// - `exception.type`: the type passed to [Error::new] or [Error::plain], as given by
+    ///   [std::any::type_name], which is not necessarily the type of the root cause: a boxed
+    ///   error reports the `Box`. Errors created from a message by `conerr!`, `bail!` and
+    ///   `ensure!` with a format string, or by [OptionExt](crate::OptionExt), report
+    ///   `conerror::Error`.
+    /// - `exception.message`: [Error::message].
+    /// - `exception.stacktrace`: a line per location, where the error occurred first,
+    ///   as written by `Display`.
+    ///
+    /// The code and kind of the error are added as `error.code` and `error.kind` if present.
+    pub fn exception_attributes(&self) -> Vec<KeyValue> {
+        let mut attributes = vec![
+            KeyValue::new("exception.type", self.0.type_name),
+            KeyValue::new("exception.message", self.message()),
+            KeyValue::new("exception.stacktrace", Stacktrace(self).to_string()),
+        ];
+        if let Some(code) = self.code() {
+            attributes.push(KeyValue::new("error.code", code.to_string()));
+        }
+        if let Some(kind) = self.kind() {
+            attributes.push(KeyValue::new("error.kind", kind.as_str()));
+        }
+        attributes
+    }
+}
+
+/// The locations of an [Error] with their context, without its message.
+struct Stacktrace<'a>(&'a Error);
+
+impl Display for Stacktrace<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format = TraceFormat::new();
+        for (j, (i, frame)) in format.frames(self.0).enumerate() {
+            if j > 0 {
+                f.write_str("\n")?;
+            }
+            format.fmt_frame(f, self.0, i, frame)?;
+        }
+        Ok(())
+    }
+}
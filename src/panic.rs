@@ -0,0 +1,144 @@
+use std::backtrace::Backtrace;
+use std::fmt::{Display, Formatter};
+use std::panic::PanicHookInfo;
+
+use crate::format::{TraceFormat, BOLD, DIM};
+use crate::report::color_enabled;
+
+/// Replaces the panic hook with one that writes panics to standard error in the format of
+/// `Display for Error`, so that panics and errors look alike:
+///
+/// ```text
+/// thread 'main' panicked: index out of bounds: the len is 3 but the index is 5
+/// #0 src/main.rs:4:5
+/// ```
+///
+/// A backtrace is written as well if `RUST_BACKTRACE` enables it, like the default hook does.
+/// Colors are used if standard error is a terminal and `NO_COLOR` is not set, or if
+/// `CLICOLOR_FORCE` is set.
+///
+/// ```
+/// conerror::install_panic_hook();
+/// let result = std::panic::catch_unwind(|| panic!("boom"));
+/// assert!(result.is_err());
+/// ```
+pub fn install_panic_hook() {
+    install_panic_hook_with(TraceFormat::new().color(color_enabled()));
+}
+
+/// Like [install_panic_hook], with the given [TraceFormat].
+///
+/// ```
+/// use conerror::format::TraceFormat;
+///
+/// conerror::install_panic_hook_with(TraceFormat::compact());
+/// ```
+pub fn install_panic_hook_with(format: TraceFormat) {
+    std::panic::set_hook(Box::new(move |info| {
+        eprintln!("{}", PanicMessage::new(info, format));
+    }));
+}
+
+/// A panic written like an [Error](crate::Error), which [install_panic_hook] writes to standard
+/// error. It can be written elsewhere by a panic hook of your own:
+///
+/// ```
+/// use conerror::format::TraceFormat;
+/// use conerror::PanicMessage;
+/// use std::panic::Location;
+/// use std::sync::Mutex;
+///
+/// static OUTPUT: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// static LOCATION: Mutex<String> = Mutex::new(String::new());
+///
+/// std::env::set_var("RUST_BACKTRACE", "0");
+/// std::panic::set_hook(Box::new(|info| {
+///     let mut output = OUTPUT.lock().unwrap();
+///     output.push(PanicMessage::new(info, TraceFormat::new()).to_string());
+///     output.push(PanicMessage::new(info, TraceFormat::compact()).to_string());
+/// }));
+///
+/// #[track_caller]
+/// fn fail(index: usize) {
+///     let caller = Location::caller();
+///     *LOCATION.lock().unwrap() = format!("{}:{}:{}", caller.file(), caller.line(), caller.column());
+///     panic!("index {} out of bounds", index);
+/// }
+///
+/// let worker = std::thread::Builder::new().name("worker".into());
+/// assert!(worker.spawn(|| fail(5)).unwrap().join().is_err());
+/// let location = LOCATION.lock().unwrap().clone();
+/// let output = std::mem::take(&mut *OUTPUT.lock().unwrap());
+/// assert_eq!(
+///     output,
+///     [
+///         format!("thread 'worker' panicked: index 5 out of bounds\n#0 {}", location),
+///         format!("thread 'worker' panicked: index 5 out of bounds | #0 {}", location),
+///     ],
+/// );
+///
+/// // Backtraces of errors are turned off, but not those of panics.
+/// std::env::set_var("RUST_BACKTRACE", "1");
+/// std::env::set_var("RUST_LIB_BACKTRACE", "0");
+/// assert!(std::thread::spawn(|| fail(5)).join().is_err());
+/// let output = std::mem::take(&mut *OUTPUT.lock().unwrap());
+/// assert!(output[0].contains("\n\nStack backtrace:\n"));
+/// assert!(!output[1].contains("Stack backtrace:"));
+/// ```
+pub struct PanicMessage<'a, 'b> {
+    info: &'a PanicHookInfo<'b>,
+    format: TraceFormat,
+    backtrace: Backtrace,
+    /// Whether the backtrace has the frames of the standard library and the runtime.
+    full: bool,
+}
+
+impl<'a, 'b> PanicMessage<'a, 'b> {
+    /// Captures a backtrace of the panic if `RUST_BACKTRACE` enables it.
+    ///
+    /// Unlike [Backtrace::capture], `RUST_LIB_BACKTRACE` is not read, so that e.g.
+    /// `RUST_BACKTRACE=1 RUST_LIB_BACKTRACE=0` only captures backtraces of panics.
+    pub fn new(info: &'a PanicHookInfo<'b>, format: TraceFormat) -> Self {
+        let style = std::env::var_os("RUST_BACKTRACE");
+        let (backtrace, full) = match style.as_ref().and_then(|v| v.to_str()) {
+            None | Some("0") => (Backtrace::disabled(), false),
+            Some(style) => (Backtrace::force_capture(), style == "full"),
+        };
+        Self {
+            info,
+            format,
+            backtrace,
+            full,
+        }
+    }
+}
+
+impl Display for PanicMessage<'_, '_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let thread = std::thread::current();
+        let name = thread.name().unwrap_or("<unnamed>");
+        let payload = self.info.payload();
+        let message = match payload.downcast_ref::<&str>() {
+            Some(v) => v,
+            None => match payload.downcast_ref::<String>() {
+                Some(v) => v.as_str(),
+                None => "Box<dyn Any>",
+            },
+        };
+        write!(f, "thread '{}' panicked: ", name)?;
+        self.format.paint(f, BOLD, message)?;
+
+        if let Some(location) = self.info.location() {
+            f.write_str(self.format.separator())?;
+            self.format.paint(f, DIM, "#0")?;
+            f.write_str(" ")?;
+            let (file, line, column) = (location.file(), location.line(), location.column());
+            self.format.fmt_position(f, file, line, column)?;
+        }
+
+        if !self.format.is_compact() {
+            self.format.fmt_captured(f, &self.backtrace, self.full)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,441 @@
+use std::borrow::Cow;
+use std::fmt::{Display, Formatter};
+
+#[cfg(feature = "serde")]
+use crate::attachment::OwnedAttachments;
+use crate::{Attachment, Error};
+
+/// The error of another process, deserialized from an [Error] it serialized.
+///
+/// Deserializing an [Error] gives an [Error] wrapping a `RemoteError`, which keeps the locations
+/// recorded by the other process. Locations recorded after that, e.g. by `?`, are added to the
+/// [Error] as usual, so the trace continues across the process boundary.
+///
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// let e = conerror::Error::new("connection refused", "src/db.rs", 40, 9, "query", "svc_a::db")
+///     .context("Failed to load user");
+/// let json = serde_json::to_string(&e).unwrap();
+///
+/// let e: conerror::Error = serde_json::from_str(&json).unwrap();
+/// let e = conerror::Error::chain(e.remote("svc-a"), "src/main.rs", 8, 13, "main", "app");
+/// assert_eq!(
+///     e.to_string(),
+///     "connection refused\n\
+///      #0 [remote svc-a] src/db.rs:40:9 svc_a::db::query() — Failed to load user\n\
+///      #1 src/main.rs:8:13 app::main()",
+/// );
+/// # }
+/// ```
+///
+/// Errors serialized by conerror 0.1.12 and earlier, or in [Schema::V1](crate::Schema::V1),
+/// have their context in the message and locations without a column:
+///
+/// ```
+/// # #[cfg(feature = "serde")]
+/// # {
+/// let json = r#"{"message":"ctx: boom","location":["src/main.rs:29 app::read()"]}"#;
+/// let e: conerror::Error = serde_json::from_str(json).unwrap();
+/// assert_eq!(e.to_string(), "ctx: boom\n#0 [remote] src/main.rs:29 app::read()");
+///
+/// let frame = &e.downcast_ref::<conerror::RemoteError>().unwrap().frames()[0];
+/// assert_eq!((frame.line, frame.column, frame.func.as_str()), (29, 0, "read"));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RemoteError {
+    pub(crate) message: String,
+    pub(crate) frames: Vec<RemoteFrame>,
+    pub(crate) source: Option<Box<RemoteCause>>,
+    /// Name of a kind unknown to this process, which is kept as it is instead of an
+    /// [ErrorKind](crate::ErrorKind).
+    pub(crate) unknown_kind: Option<String>,
+}
+
+impl RemoteError {
+    /// Returns the message of the error wrapped by the remote [Error], without its context.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the locations recorded by the remote process, where the error occurred first.
+    pub fn frames(&self) -> &[RemoteFrame] {
+        &self.frames
+    }
+
+    /// Returns the name of the kind of the remote error if it is not known to this process,
+    /// e.g. an [ErrorKind::Custom](crate::ErrorKind::Custom) kind of the remote process.
+    ///
+    /// Known kinds are returned by [Error::kind] instead.
+    pub fn unknown_kind(&self) -> Option<&str> {
+        self.unknown_kind.as_deref()
+    }
+}
+
+impl Display for RemoteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RemoteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_deref()?)
+    }
+}
+
+/// An error in the source chain of a [RemoteError], of which only the message is known.
+#[derive(Debug)]
+pub(crate) struct RemoteCause {
+    message: String,
+    source: Option<Box<RemoteCause>>,
+}
+
+#[cfg(feature = "serde")]
+impl RemoteCause {
+    /// Links messages into a source chain, the outermost first.
+    fn chain(messages: impl DoubleEndedIterator<Item = String>) -> Option<Box<Self>> {
+        messages.rev().fold(None, |source, message| {
+            Some(Box::new(Self { message, source }))
+        })
+    }
+}
+
+impl Display for RemoteCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for RemoteCause {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_deref()?)
+    }
+}
+
+/// A location recorded by another process, see [RemoteError].
+#[derive(Debug)]
+pub struct RemoteFrame {
+    /// Name of the service the location was recorded by, see [Error::remote].
+    pub service: Option<Cow<'static, str>>,
+    pub file: String,
+    pub line: u32,
+    /// 0 if unknown, for errors serialized by conerror 0.1.12 and earlier.
+    pub column: u32,
+    pub func: String,
+    pub module: String,
+    /// Context added at the location, the last added first.
+    pub context: Vec<String>,
+    pub attachments: Vec<Attachment>,
+}
+
+impl Display for RemoteFrame {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)?;
+        if self.column != 0 {
+            write!(f, ":{}", self.column)?;
+        }
+        match (self.func.as_str(), self.module.as_str()) {
+            ("", _) => Ok(()),
+            (func, "") => write!(f, " {}()", func),
+            (func, module) => write!(f, " {}::{}()", module, func),
+        }
+    }
+}
+
+impl Error {
+    /// Names the service a deserialized error came from, which is written before its locations,
+    /// e.g. `#0 [remote svc-a] src/db.rs:40:9 svc_a::db::query()`.
+    ///
+    /// Locations already named by a service the error passed through before are kept as they
+    /// are. Errors not deserialized from another process are returned unchanged.
+    pub fn remote(mut self, service: impl Into<Cow<'static, str>>) -> Self {
+        if let Some(remote) = self.0.source.downcast_mut::<RemoteError>() {
+            let service = service.into();
+            for v in remote.frames.iter_mut().filter(|v| v.service.is_none()) {
+                v.service = Some(service.clone());
+            }
+        }
+        self
+    }
+
+    /// Returns the error of another process this error was deserialized from.
+    pub(crate) fn remote_error(&self) -> Option<&RemoteError> {
+        self.0.source.downcast_ref()
+    }
+}
+
+/// A deserialized location, written as an object `{file, line, column, func, module}`,
+/// or as a string `file:line module::func()` by [Schema::V1](crate::Schema::V1), which may
+/// have a column as well.
+#[cfg(feature = "serde")]
+#[derive(Default)]
+struct OwnedLocation {
+    file: String,
+    line: u32,
+    column: u32,
+    func: String,
+    module: String,
+}
+
+#[cfg(feature = "serde")]
+impl OwnedLocation {
+    fn parse(s: &str) -> Option<Self> {
+        // The file may contain `:` itself, e.g. `C:\src\lib.rs`.
+        s.match_indices(':').find_map(|(i, _)| {
+            let (line, rest) = split_number(&s[i + 1..])?;
+            let (column, rest) = match rest.strip_prefix(':') {
+                Some(rest) => split_number(rest)?,
+                None => (0, rest),
+            };
+            let function = match rest {
+                "" => "",
+                rest => rest.strip_prefix(' ')?,
+            };
+            let function = function.strip_suffix("()").unwrap_or(function);
+            let (module, func) = function.rsplit_once("::").unwrap_or(("", function));
+            Some(Self {
+                file: s[..i].to_string(),
+                line,
+                column,
+                func: func.to_string(),
+                module: module.to_string(),
+            })
+        })
+    }
+
+    fn parse_str<E>(s: &str) -> Result<Self, E>
+    where
+        E: serde::de::Error,
+    {
+        Self::parse(s).ok_or_else(|| E::custom(format!("invalid location `{}`", s)))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl RemoteFrame {
+    fn new(
+        service: Option<Cow<'static, str>>,
+        location: OwnedLocation,
+        context: Vec<String>,
+        attachments: Vec<Attachment>,
+    ) -> Self {
+        Self {
+            service,
+            file: location.file,
+            line: location.line,
+            column: location.column,
+            func: location.func,
+            module: location.module,
+            context,
+            attachments,
+        }
+    }
+}
+
+/// Splits a leading number off a string.
+#[cfg(feature = "serde")]
+fn split_number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OwnedLocation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = OwnedLocation;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a location")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                OwnedLocation::parse_str(v)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut location = OwnedLocation::default();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "file" => location.file = map.next_value()?,
+                        "line" => location.line = map.next_value()?,
+                        "column" => location.column = map.next_value()?,
+                        "func" => location.func = map.next_value()?,
+                        "module" => location.module = map.next_value()?,
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+                Ok(location)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RemoteFrame {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = RemoteFrame;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a location")
+            }
+
+            // Written by conerror 0.1.12 and earlier, and by `Schema::V1`.
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let location = OwnedLocation::parse_str(v)?;
+                Ok(RemoteFrame::new(None, location, Vec::new(), Vec::new()))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                use serde::de::Error;
+
+                let mut location = None;
+                let mut service = None;
+                let mut context = Vec::new();
+                let mut attachments = Vec::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "location" => location = Some(map.next_value::<OwnedLocation>()?),
+                        "service" => service = map.next_value::<Option<String>>()?,
+                        "context" => context = map.next_value()?,
+                        "attachments" => attachments = map.next_value::<OwnedAttachments>()?.0,
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                let location = location.ok_or_else(|| A::Error::missing_field("location"))?;
+                let service = service.map(Cow::Owned);
+                Ok(RemoteFrame::new(service, location, context, attachments))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Error {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Error;
+
+            fn expecting(&self, f: &mut Formatter) -> std::fmt::Result {
+                f.write_str("a serialized conerror::Error")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                use serde::de::Error as _;
+
+                let mut version = 1;
+                let mut message = None;
+                let mut source = None;
+                let mut sources = Vec::<String>::new();
+                let mut code = None;
+                let mut kind = None;
+                let mut context = Vec::<String>::new();
+                let mut frames = Vec::new();
+                let mut attachments = Vec::new();
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "version" => version = map.next_value::<u32>()?,
+                        "message" => message = Some(map.next_value::<String>()?),
+                        "sources" => sources = map.next_value()?,
+                        "source" => source = Some(map.next_value::<String>()?),
+                        "code" => code = map.next_value::<Option<String>>()?,
+                        "kind" => kind = map.next_value::<Option<String>>()?,
+                        "context" => context = map.next_value()?,
+                        "location" => frames = map.next_value()?,
+                        "attachments" => attachments = map.next_value::<OwnedAttachments>()?.0,
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?;
+                        }
+                    }
+                }
+
+                if version > crate::Schema::default().version() {
+                    let message = format!("unsupported schema version {}", version);
+                    return Err(A::Error::custom(message));
+                }
+
+                // Without `sources` or `source`, the context is part of the message.
+                let mut sources = sources.into_iter();
+                let message = sources
+                    .next()
+                    .or(source)
+                    .or(message)
+                    .ok_or_else(|| A::Error::missing_field("message"))?;
+                let source = RemoteCause::chain(sources);
+                // Unknown kinds are not turned into `ErrorKind::Custom`, which needs a
+                // `&'static str`.
+                let (kind, unknown_kind) = match kind {
+                    Some(name) => match crate::ErrorKind::from_name(&name) {
+                        Some(kind) => (Some(kind), None),
+                        None => (None, Some(name)),
+                    },
+                    None => (None, None),
+                };
+                let mut error = Error::plain(RemoteError {
+                    message,
+                    frames,
+                    source,
+                    unknown_kind,
+                });
+                error.0.location = Some(Vec::new());
+                #[cfg(feature = "opentelemetry")]
+                {
+                    error.0.type_name = "conerror::RemoteError";
+                }
+                error.0.code = code.map(Cow::Owned);
+                error.0.kind = kind;
+                for v in context.into_iter().rev() {
+                    error = error.context(v);
+                }
+                for v in attachments {
+                    error.push_context(crate::ContextValue::Attachment(v));
+                }
+                Ok(error)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
@@ -0,0 +1,368 @@
+//! Rendering of an [Error] for terminals and for the result of `main`.
+
+use std::fmt::{Debug, Display, Formatter};
+use std::io::IsTerminal;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{ExitCode, Termination};
+
+use crate::format::{Frame, TraceFormat, BOLD, DIM};
+use crate::{Error, ErrorTrait, Location};
+
+const RED: &str = "1;31";
+
+/// Renders an [Error] for people reading a terminal, with the source code around each location.
+///
+/// Apart from a backtrace captured with the `backtrace` feature, which is written last,
+/// the output only depends on the error, the options and the source files, so it can be
+/// compared as is:
+///
+/// ```
+/// use conerror::report::Renderer;
+///
+/// let root = std::env::temp_dir().join(format!("conerror-renderer-{}", std::process::id()));
+/// std::fs::create_dir_all(&root).unwrap();
+/// let manifest = "[package]\nname = \"conerror\"\n\tversion = \"0.1\"\nedition = \"2021\"\n";
+/// std::fs::write(root.join("Cargo.toml"), manifest).unwrap();
+///
+/// std::env::set_var("RUST_LIB_BACKTRACE", "0");
+/// let e = conerror::Error::new("invalid version", "Cargo.toml", 3, 12, "check", "build")
+///     .context("Failed to load manifest");
+/// let renderer = Renderer::new().color(false).context_lines(1).root(&root);
+/// let report = renderer.render(&e).to_string();
+/// std::fs::remove_dir_all(&root).unwrap();
+///
+/// assert_eq!(
+///     report,
+///     [
+///         "Error: invalid version",
+///         "",
+///         "Locations:",
+///         "  #0 Cargo.toml:3:12 build::check() — Failed to load manifest",
+///         "    2 | name = \"conerror\"",
+///         "    3 | \tversion = \"0.1\"",
+///         "      | \t          ^",
+///         "    4 | edition = \"2021\"",
+///     ]
+///     .join("\n"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Renderer {
+    format: TraceFormat,
+    snippets: bool,
+    context_lines: usize,
+    root: Option<PathBuf>,
+}
+
+impl Renderer {
+    /// Creates a renderer showing two lines of source code before and after each location.
+    ///
+    /// Colors are used if standard error is a terminal and `NO_COLOR` is not set, or if
+    /// `CLICOLOR_FORCE` is set:
+    ///
+    /// ```
+    /// use conerror::report::Renderer;
+    ///
+    /// # let root = std::env::temp_dir().join(format!("conerror-renderer-{}", std::process::id()));
+    /// # std::fs::create_dir_all(&root).unwrap();
+    /// # let manifest = "[package]\nname = \"conerror\"\n\tversion = \"0.1\"\nedition = \"2021\"\n";
+    /// # std::fs::write(root.join("Cargo.toml"), manifest).unwrap();
+    /// std::env::set_var("RUST_LIB_BACKTRACE", "0");
+    /// let e = conerror::Error::new("invalid version", "Cargo.toml", 3, 12, "check", "build")
+    ///     .context("Failed to load manifest");
+    ///
+    /// std::env::set_var("CLICOLOR_FORCE", "1");
+    /// let report = Renderer::new().context_lines(0).root(&root).render(&e).to_string();
+    /// assert_eq!(
+    ///     report,
+    ///     [
+    ///         "\x1b[1;31mError:\x1b[0m \x1b[1minvalid version\x1b[0m",
+    ///         "",
+    ///         "\x1b[1mLocations:\x1b[0m",
+    ///         "  \x1b[2m#0\x1b[0m \x1b[36mCargo.toml:3:12\x1b[0m \x1b[32mbuild::check()\x1b[0m \
+    ///             — \x1b[33mFailed to load manifest\x1b[0m",
+    ///         "    \x1b[1m3 |\x1b[0m \tversion = \"0.1\"",
+    ///         "    \x1b[2m  |\x1b[0m \t          \x1b[1;31m^\x1b[0m",
+    ///     ]
+    ///     .join("\n"),
+    /// );
+    ///
+    /// std::env::set_var("NO_COLOR", "1");
+    /// let report = Renderer::new().root(&root).render(&e).to_string();
+    /// assert!(!report.contains('\x1b'));
+    /// # std::fs::remove_dir_all(&root).unwrap();
+    /// ```
+    pub fn new() -> Self {
+        Self {
+            format: TraceFormat::new().color(color_enabled()),
+            snippets: true,
+            context_lines: 2,
+            root: None,
+        }
+    }
+
+    /// Sets the format of the message and the locations.
+    ///
+    /// The [layout](TraceFormat::layout) is ignored, every location is written on its own line.
+    pub fn format(mut self, format: TraceFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets whether ANSI colors are used.
+    pub fn color(mut self, color: bool) -> Self {
+        self.format = self.format.color(color);
+        self
+    }
+
+    /// Sets whether the source code around each location is shown, if its file can be read.
+    pub fn snippets(mut self, snippets: bool) -> Self {
+        self.snippets = snippets;
+        self
+    }
+
+    /// Sets the number of lines shown before and after the line of a location.
+    pub fn context_lines(mut self, lines: usize) -> Self {
+        self.context_lines = lines;
+        self
+    }
+
+    /// Sets the directory that relative paths of locations are read from,
+    /// the working directory by default.
+    ///
+    /// `file!()` is relative to the root of the workspace the crate was built in.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Returns a value that renders the error when displayed.
+    pub fn render<'a>(&'a self, error: &'a Error) -> Rendered<'a> {
+        Rendered {
+            renderer: self,
+            error,
+        }
+    }
+
+    fn fmt(&self, f: &mut Formatter<'_>, error: &Error) -> std::fmt::Result {
+        self.format.paint(f, RED, "Error:")?;
+        f.write_str(" ")?;
+        self.format.fmt_message(f, error)?;
+
+        for (j, (i, frame)) in self.format.frames(error).enumerate() {
+            if j == 0 {
+                f.write_str("\n\n")?;
+                self.format.paint(f, BOLD, "Locations:")?;
+            }
+            f.write_str("\n  ")?;
+            self.format.fmt_frame(f, error, i, frame)?;
+            // The source of another process is not at hand.
+            if let (true, Frame::Local(_, location)) = (self.snippets, frame) {
+                self.fmt_snippet(f, location)?;
+            }
+        }
+
+        for (i, v) in error.sources().skip(1).enumerate() {
+            if i == 0 {
+                f.write_str("\n\n")?;
+                self.format.paint(f, BOLD, "Caused by:")?;
+            }
+            write!(f, "\n    {}: {}", i, v)?;
+        }
+
+        #[cfg(feature = "backtrace")]
+        self.format.fmt_backtrace(f, error)?;
+        #[cfg(feature = "spantrace")]
+        self.format.fmt_span_trace(f, error)?;
+        Ok(())
+    }
+
+    /// Writes the lines around a location with a caret under its column.
+    fn fmt_snippet(&self, f: &mut Formatter<'_>, location: &Location) -> std::fmt::Result {
+        let path = match self.root {
+            Some(ref root) => root.join(location.file),
+            None => Path::new(location.file).to_path_buf(),
+        };
+        let Ok(source) = std::fs::read_to_string(path) else {
+            return Ok(());
+        };
+        let Some(line) = (location.line as usize).checked_sub(1) else {
+            return Ok(());
+        };
+        let lines: Vec<_> = source.lines().collect();
+        if line >= lines.len() {
+            return Ok(());
+        }
+
+        let first = line.saturating_sub(self.context_lines);
+        let last = (line + self.context_lines).min(lines.len() - 1);
+        let width = (last + 1).to_string().len();
+        for (i, text) in lines.iter().enumerate().take(last + 1).skip(first) {
+            f.write_str("\n    ")?;
+            let style = if i == line { BOLD } else { DIM };
+            self.format
+                .paint(f, style, format_args!("{:>width$} |", i + 1))?;
+            if !text.is_empty() {
+                write!(f, " {}", text)?;
+            }
+            if i == line {
+                // Tabs are kept so that the caret lines up with the column.
+                let indent: String = text
+                    .chars()
+                    .take((location.column as usize).saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                f.write_str("\n    ")?;
+                self.format
+                    .paint(f, DIM, format_args!("{:>width$} |", ""))?;
+                write!(f, " {}", indent)?;
+                self.format.paint(f, RED, "^")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns `true` if `NO_COLOR` is not set to a non-empty value, and either `CLICOLOR_FORCE` is
+/// set to a value other than `0` or standard error is a terminal.
+pub(crate) fn color_enabled() -> bool {
+    if std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return false;
+    }
+    std::env::var_os("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0")
+        || std::io::stderr().is_terminal()
+}
+
+/// An [Error] rendered by a [Renderer], see [Renderer::render].
+pub struct Rendered<'a> {
+    renderer: &'a Renderer,
+    error: &'a Error,
+}
+
+impl Display for Rendered<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.renderer.fmt(f, self.error)
+    }
+}
+
+/// An [Error] whose `Debug` writes the same as `{:#}`, i.e. the locations and the source chain,
+/// instead of the fields of the error.
+///
+/// Any error converts into a `Report`, which can be returned from `main` as
+/// `Result<(), Report>`. Use [MainResult] to also exit with a code given by the error.
+///
+/// ```
+/// let report = conerror::Report::from(conerror::Error::plain("not found"));
+/// assert!(format!("{:?}", report).starts_with("not found"));
+/// ```
+pub struct Report(Error);
+
+impl Report {
+    /// Returns the wrapped error.
+    pub fn into_inner(self) -> Error {
+        self.0
+    }
+}
+
+impl<E> From<E> for Report
+where
+    E: ErrorTrait + 'static,
+{
+    /// Converts an error into a [Report], located at the caller unless it is already an [Error].
+    #[track_caller]
+    fn from(error: E) -> Self {
+        Self(Error::caller(error))
+    }
+}
+
+impl Deref for Report {
+    type Target = Error;
+
+    fn deref(&self) -> &Error {
+        &self.0
+    }
+}
+
+impl Debug for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+/// Return type of `main` that writes an error to standard error like [Report],
+/// and exits with the code given by [ErrorKind::exit_code](crate::ErrorKind::exit_code),
+/// or 1 for errors without a kind.
+///
+/// ```
+/// use conerror::{conerror, ErrorKind, MainResult};
+/// use std::process::{ExitCode, Termination};
+///
+/// #[conerror(kind = NotFound)]
+/// fn run() -> conerror::Result<()> {
+///     std::fs::read("non_exists_config.toml")?;
+///     Ok(())
+/// }
+///
+/// // fn main() -> MainResult
+/// let result: MainResult = run().into();
+/// assert_eq!(result.report(), ExitCode::from(66));
+/// let result = MainResult::from(run()).exit_code(|e| match e.code() {
+///     Some("CONFIG") => 78,
+///     _ => 1,
+/// });
+/// assert_eq!(result.report(), ExitCode::from(1));
+/// ```
+pub struct MainResult {
+    result: Result<(), Report>,
+    exit_code: fn(&Error) -> u8,
+}
+
+impl MainResult {
+    /// Sets the function that gives the exit code of an error.
+    pub fn exit_code(mut self, exit_code: fn(&Error) -> u8) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+}
+
+impl<E> From<Result<(), E>> for MainResult
+where
+    E: ErrorTrait + 'static,
+{
+    #[track_caller]
+    fn from(result: Result<(), E>) -> Self {
+        let result = match result {
+            Ok(()) => Ok(()),
+            Err(e) => Err(Report::from(e)),
+        };
+        Self {
+            result,
+            exit_code: |e| e.kind().map_or(1, |v| v.exit_code()),
+        }
+    }
+}
+
+impl Termination for MainResult {
+    fn report(self) -> ExitCode {
+        match self.result {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                ExitCode::from((self.exit_code)(&e))
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::attachment::Attachments;
+use crate::{Error, Location, RemoteFrame};
+
+/// Version of the serialized form of an [Error], see [Error::serialize_as].
+///
+/// ```
+/// use conerror::Schema;
+///
+/// let e = conerror::Error::new("not found", "src/lib.rs", 3, 5, "read", "app::db");
+/// let v2 = serde_json::to_value(&e).unwrap();
+/// assert_eq!(v2["version"], 2);
+/// assert_eq!(v2["sources"][0], "not found");
+/// assert_eq!(
+///     v2["location"][0]["location"],
+///     serde_json::json!({
+///         "file": "src/lib.rs",
+///         "line": 3,
+///         "column": 5,
+///         "func": "read",
+///         "module": "app::db",
+///     }),
+/// );
+///
+/// let v1 = serde_json::to_value(e.context("Failed to read").serialize_as(Schema::V1)).unwrap();
+/// assert_eq!(
+///     v1,
+///     serde_json::json!({
+///         "message": "Failed to read: not found",
+///         "location": ["src/lib.rs:3 app::db::read()"],
+///     }),
+/// );
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum Schema {
+    /// The format of conerror 0.1.12 and earlier: the message with its context, and each
+    /// location as a string `file:line module::func()`. It has no `version` field, and no
+    /// columns, code, kind or attachments.
+    V1,
+    /// Each location as an object `{file, line, column, func, module}`, and the wrapped error
+    /// followed by its source chain as `sources`. The default, which serializing an [Error]
+    /// gives.
+    #[default]
+    V2,
+}
+
+impl Schema {
+    /// Returns the number written as the `version` field.
+    pub fn version(&self) -> u32 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+}
+
+impl Error {
+    /// Returns a value that serializes the error in the given [Schema],
+    /// e.g. for consumers of the format of earlier releases.
+    pub fn serialize_as(&self, schema: Schema) -> Serialized<'_> {
+        Serialized {
+            error: self,
+            schema,
+        }
+    }
+}
+
+/// An [Error] serialized in a [Schema], see [Error::serialize_as].
+pub struct Serialized<'a> {
+    error: &'a Error,
+    schema: Schema,
+}
+
+/// A location of either [Schema].
+enum SerializeLocation<'a> {
+    Local(&'a Location),
+    Remote(&'a RemoteFrame),
+}
+
+impl serde::Serialize for SerializeLocation<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Self::Local(v) => v.serialize(serializer),
+            Self::Remote(v) => {
+                let position = (v.file.as_str(), v.line, v.column);
+                serialize_location(serializer, position, &v.func, &v.module)
+            }
+        }
+    }
+}
+
+/// A location with its context.
+struct SerializeFrame<'a> {
+    service: Option<&'a str>,
+    location: SerializeLocation<'a>,
+    context: Vec<&'a str>,
+    attachments: Attachments<'a>,
+}
+
+impl serde::Serialize for SerializeFrame<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("Frame", 4)?;
+        match self.service {
+            Some(service) => s.serialize_field("service", service)?,
+            None => s.skip_field("service")?,
+        }
+        s.serialize_field("location", &self.location)?;
+        s.serialize_field("context", &self.context)?;
+        s.serialize_field("attachments", &self.attachments)?;
+        s.end()
+    }
+}
+
+impl serde::Serialize for Serialized<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let error = self.error;
+        if self.schema == Schema::V1 {
+            return serialize_v1(serializer, error);
+        }
+
+        let mut s = serializer.serialize_struct("Error", 8)?;
+        s.serialize_field("version", &self.schema.version())?;
+        s.serialize_field("message", &error.message())?;
+        let sources: Vec<_> = error.sources().map(|v| v.to_string()).collect();
+        s.serialize_field("sources", &sources)?;
+        s.serialize_field("code", &error.0.code)?;
+        let unknown_kind = error.remote_error().and_then(|v| v.unknown_kind());
+        let kind = error.kind().map(|v| v.as_str()).or(unknown_kind);
+        s.serialize_field("kind", &kind)?;
+        s.serialize_field("context", &error.frame_messages(None).collect::<Vec<_>>())?;
+
+        // Locations recorded by another process come first, where the error occurred.
+        let remote = error.remote_error().map(|v| v.frames()).unwrap_or_default();
+        let remote = remote.iter().map(|v| SerializeFrame {
+            service: v.service.as_deref(),
+            location: SerializeLocation::Remote(v),
+            context: v.context.iter().map(String::as_str).collect(),
+            attachments: Attachments(v.attachments.iter().collect()),
+        });
+        let local = error.location().unwrap_or_default();
+        let local = local.iter().enumerate().map(|(i, v)| SerializeFrame {
+            service: None,
+            location: SerializeLocation::Local(v),
+            context: error.frame_messages(Some(i)).collect(),
+            attachments: Attachments(error.frame_attachments(Some(i)).collect()),
+        });
+        s.serialize_field("location", &remote.chain(local).collect::<Vec<_>>())?;
+        s.serialize_field(
+            "attachments",
+            &Attachments(error.frame_attachments(None).collect()),
+        )?;
+        s.end()
+    }
+}
+
+/// Serializes an error as `{message, location}`, with each location as `file:line module::func()`.
+fn serialize_v1<S>(serializer: S, error: &Error) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    use serde::ser::SerializeStruct;
+
+    fn location(file: &str, line: u32, func: &str, module: &str) -> String {
+        match (func, module) {
+            ("", _) => format!("{}:{}", file, line),
+            (func, "") => format!("{}:{} {}()", file, line, func),
+            (func, module) => format!("{}:{} {}::{}()", file, line, module, func),
+        }
+    }
+
+    let remote = error.remote_error().map(|v| v.frames()).unwrap_or_default();
+    let remote = remote
+        .iter()
+        .map(|v| location(&v.file, v.line, &v.func, &v.module));
+    let local = error.location().unwrap_or_default();
+    let local = local
+        .iter()
+        .map(|v| location(v.file, v.line, v.func, v.module));
+    let mut s = serializer.serialize_struct("Error", 2)?;
+    s.serialize_field("message", &error.message())?;
+    s.serialize_field("location", &remote.chain(local).collect::<Vec<_>>())?;
+    s.end()
+}
+
+impl serde::Serialize for Error {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.serialize_as(Schema::default()).serialize(serializer)
+    }
+}
+
+impl serde::Serialize for Location {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let position = (self.file, self.line, self.column);
+        serialize_location(serializer, position, self.func, self.module)
+    }
+}
+
+/// Serializes a location as `{file, line, column, func, module}`.
+fn serialize_location<S>(
+    serializer: S,
+    (file, line, column): (&str, u32, u32),
+    func: &str,
+    module: &str,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    use serde::ser::SerializeStruct;
+
+    let mut s = serializer.serialize_struct("Location", 5)?;
+    s.serialize_field("file", file)?;
+    s.serialize_field("line", &line)?;
+    s.serialize_field("column", &column)?;
+    s.serialize_field("func", func)?;
+    s.serialize_field("module", module)?;
+    s.end()
+}
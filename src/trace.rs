@@ -0,0 +1,153 @@
+use valuable::{
+    Fields, Listable, NamedField, NamedValues, StructDef, Structable, Valuable, Value, Visit,
+};
+
+use crate::format::{Frame, TraceFormat};
+use crate::Error;
+
+/// Emits a `DEBUG` event for an error passed on by a `?` rewritten by `#[conerror]`.
+///
+/// The fields follow the OpenTelemetry conventions for source code attributes.
+pub(crate) fn propagated(
+    error: &Error,
+    file: &'static str,
+    line: u32,
+    column: u32,
+    func: &'static str,
+    module: &'static str,
+) {
+    tracing::debug!(
+        target: "conerror",
+        error = %error.message(),
+        error.code = error.code(),
+        error.kind = error.kind().map(|v| v.as_str()),
+        code.filepath = file,
+        code.lineno = line,
+        code.column = column,
+        code.function = %format_args!("{}::{}", module, func),
+        "error propagated",
+    );
+}
+
+static ERROR_FIELDS: &[NamedField<'static>] = &[
+    NamedField::new("message"),
+    NamedField::new("code"),
+    NamedField::new("kind"),
+    NamedField::new("context"),
+    NamedField::new("location"),
+];
+
+/// Gives the message, code, kind, context and locations as a structure, so that e.g.
+/// `tracing` subscribers can record an error with `error = e.as_value()`.
+///
+/// `tracing` only records `valuable` values when built with `RUSTFLAGS="--cfg tracing_unstable"`;
+/// otherwise `e.as_value()` is not accepted as a field value.
+///
+/// ```
+/// use valuable::Valuable;
+///
+/// let e = conerror::Error::new("not found", "src/lib.rs", 3, 5, "read", "app::db");
+/// assert!(matches!(e.as_value(), valuable::Value::Structable(_)));
+/// ```
+impl Valuable for Error {
+    fn as_value(&self) -> Value<'_> {
+        Value::Structable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        let message = self.message();
+        let context: Vec<_> = self.frame_messages(None).collect();
+        visit.visit_named_fields(&NamedValues::new(
+            ERROR_FIELDS,
+            &[
+                Value::String(&message),
+                self.code().map_or(Value::Unit, Value::String),
+                self.kind()
+                    .map_or(Value::Unit, |v| Value::String(v.as_str())),
+                context.as_value(),
+                Value::Listable(&Frames(self)),
+            ],
+        ));
+    }
+}
+
+impl Structable for Error {
+    fn definition(&self) -> StructDef<'_> {
+        StructDef::new_static("Error", Fields::Named(ERROR_FIELDS))
+    }
+}
+
+/// The locations of an [Error], where it occurred first.
+struct Frames<'a>(&'a Error);
+
+impl Valuable for Frames<'_> {
+    fn as_value(&self) -> Value<'_> {
+        Value::Listable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        for (_, frame) in TraceFormat::new().frames(self.0) {
+            visit.visit_value(FrameValue(self.0, frame).as_value());
+        }
+    }
+}
+
+impl Listable for Frames<'_> {
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remote = self.0.remote_error().map_or(0, |v| v.frames().len());
+        let len = remote + self.0.location().map_or(0, |v| v.len());
+        (len, Some(len))
+    }
+}
+
+static FRAME_FIELDS: &[NamedField<'static>] = &[
+    NamedField::new("service"),
+    NamedField::new("file"),
+    NamedField::new("line"),
+    NamedField::new("column"),
+    NamedField::new("func"),
+    NamedField::new("module"),
+    NamedField::new("context"),
+];
+
+/// A location with its context.
+struct FrameValue<'a>(&'a Error, Frame<'a>);
+
+impl Valuable for FrameValue<'_> {
+    fn as_value(&self) -> Value<'_> {
+        Value::Structable(self)
+    }
+
+    fn visit(&self, visit: &mut dyn Visit) {
+        let local;
+        let (service, file, line, column, func, module, context) = match self.1 {
+            Frame::Local(i, v) => {
+                local = self.0.frame_messages(Some(i)).collect::<Vec<_>>();
+                let context = local.as_value();
+                (None, v.file, v.line, v.column, v.func, v.module, context)
+            }
+            Frame::Remote(v) => {
+                let (file, func, module) = (v.file.as_str(), v.func.as_str(), v.module.as_str());
+                let context = v.context.as_value();
+                let service = v.service.as_deref();
+                (service, file, v.line, v.column, func, module, context)
+            }
+        };
+        let values = [
+            service.map_or(Value::Unit, Value::String),
+            Value::String(file),
+            Value::U32(line),
+            Value::U32(column),
+            Value::String(func),
+            Value::String(module),
+            context,
+        ];
+        visit.visit_named_fields(&NamedValues::new(FRAME_FIELDS, &values));
+    }
+}
+
+impl Structable for FrameValue<'_> {
+    fn definition(&self) -> StructDef<'_> {
+        StructDef::new_static("Frame", Fields::Named(FRAME_FIELDS))
+    }
+}
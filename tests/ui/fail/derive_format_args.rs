@@ -0,0 +1,7 @@
+#[derive(Debug, conerror::Error)]
+enum AppError {
+    #[error("wrapped {} {0}", extra)]
+    Wrapped(String, u32),
+}
+
+fn main() {}
@@ -0,0 +1,21 @@
+use conerror::conerror;
+
+struct App;
+
+#[conerror(context = "Failed to load")]
+impl App {
+    fn load(&self) -> conerror::Result<Vec<u8>> {
+        Ok(std::fs::read("config.toml")?)
+    }
+}
+
+#[conerror]
+impl App {
+    fn save(&self) -> conerror::Result<()> {
+        #[conerror(code = "SAVE")]
+        std::fs::write("config.toml", "")?;
+        Ok(())
+    }
+}
+
+fn main() {}
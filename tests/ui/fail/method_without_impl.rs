@@ -0,0 +1,12 @@
+use conerror::conerror;
+
+struct App;
+
+impl App {
+    #[conerror]
+    fn load(&self) -> conerror::Result<Vec<u8>> {
+        Ok(std::fs::read("config.toml")?)
+    }
+}
+
+fn main() {}
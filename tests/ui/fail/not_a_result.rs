@@ -0,0 +1,10 @@
+use conerror::conerror;
+use std::task::Poll;
+
+#[conerror]
+fn poll() -> conerror::Result<Poll<usize>> {
+    let len = Poll::Ready(Ok::<_, std::io::Error>(1))?;
+    Ok(len.map(|v| v + 1))
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use conerror::conerror;
+
+struct App;
+
+impl App {
+    #[conerror]
+    fn new() -> conerror::Result<Self> {
+        std::fs::read("config.toml")?;
+        Ok(App)
+    }
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use conerror::conerror;
+
+#[conerror(skip)]
+fn load() -> conerror::Result<Vec<u8>> {
+    Ok(std::fs::read("config.toml")?)
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use conerror::conerror;
+
+#[conerror(args(path, user))]
+fn load(path: &str) -> conerror::Result<Vec<u8>> {
+    Ok(std::fs::read(path)?)
+}
+
+fn main() {}
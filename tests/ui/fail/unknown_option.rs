@@ -0,0 +1,8 @@
+use conerror::conerror;
+
+#[conerror(message = "Failed to load")]
+fn load() -> conerror::Result<Vec<u8>> {
+    Ok(std::fs::read("config.toml")?)
+}
+
+fn main() {}
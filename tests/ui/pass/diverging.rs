@@ -0,0 +1,36 @@
+#![deny(warnings)]
+
+use conerror::conerror;
+
+#[conerror(context = "Failed to watch {path}")]
+fn watch(path: &str) -> conerror::Result<()> {
+    loop {
+        std::fs::read(path)?;
+    }
+}
+
+#[conerror(context = "Failed to load {path}")]
+fn load(path: &str, cached: bool) -> conerror::Result<Vec<u8>> {
+    if cached {
+        return loop {
+            break Ok(std::fs::read(path)?);
+        };
+    }
+    if path.is_empty() {
+        return Err(conerror::conerr!("no path given"));
+    }
+    todo!()
+}
+
+#[conerror(context = "Failed to check {path}")]
+fn check(path: &str) -> conerror::Result<()> {
+    if path.is_empty() {
+        return Ok(());
+    } else {
+        panic!("unsupported path {}", path);
+    }
+}
+
+fn main() {
+    let _ = (watch, load, check);
+}
@@ -0,0 +1,41 @@
+#![deny(warnings)]
+
+use conerror::conerror;
+
+struct App;
+
+#[conerror]
+impl App {
+    const PATH: &'static str = "config.toml";
+
+    fn load(&self) -> conerror::Result<Vec<u8>> {
+        fn read(path: &str) -> conerror::Result<Vec<u8>> {
+            Ok(std::fs::read(path)?)
+        }
+
+        let len = |v: &[u8]| -> Option<usize> { Some(v.first()?.count_ones() as usize) };
+        let data = read(Self::PATH)?;
+        let _ = len(&data);
+        Ok(data)
+    }
+
+    fn first(&self, v: &[u8]) -> Option<u8> {
+        Some(*v.first()?)
+    }
+
+    #[conerror(skip)]
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(std::fs::metadata(Self::PATH)?.len())
+    }
+}
+
+impl App {
+    #[conerror]
+    fn open(path: &str) -> conerror::Result<std::fs::File> {
+        Ok(std::fs::File::open(path)?)
+    }
+}
+
+fn main() {
+    let _ = (App.load(), App.first(&[]), App.size(), App::open("config.toml"));
+}